@@ -0,0 +1,295 @@
+use std::{
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    thread,
+};
+
+use crate::{
+    BloodPressureReadingExtractor,
+    debug::BpmOcrDebugOutputter,
+    decode_image_buffer,
+    models::{BloodPressureReading, DebuggerTrace, ProcessingError, ReadingIdentificationError},
+    read_image_file,
+};
+
+/// A debug trace name for a photo file. Directories are flattened into the name so that photos with
+/// the same file name in different directories don't share a debug folder. Any `_` or `%` already in
+/// the path is percent encoded first, so only a flattened directory separator becomes a `_`.
+pub fn trace_name_for_path(path: &Path) -> String {
+    path.to_string_lossy()
+        .trim_start_matches(['/', '\\'])
+        .replace('%', "%25")
+        .replace('_', "%5F")
+        .replace(['/', '\\'], "_")
+}
+
+/// Where to load an image in a batch from
+pub enum ImageSource {
+    File(PathBuf),
+    Buffer(Vec<u8>),
+}
+
+/// An image to extract a reading from as part of a batch, along with the name of the debug trace to
+/// output its debug images under
+pub struct BatchInput {
+    pub unique_trace_name: String,
+    pub source: ImageSource,
+}
+
+impl BatchInput {
+    /// A photo file, traced under its path
+    pub fn from_file(path: PathBuf) -> Self {
+        BatchInput {
+            unique_trace_name: trace_name_for_path(&path),
+            source: ImageSource::File(path),
+        }
+    }
+
+    pub fn from_buffer(unique_trace_name: &str, file_contents: Vec<u8>) -> Self {
+        BatchInput {
+            unique_trace_name: unique_trace_name.to_owned(),
+            source: ImageSource::Buffer(file_contents),
+        }
+    }
+}
+
+/// The number of workers to use when the caller has no preference, i.e. one per available core
+pub fn default_worker_count() -> usize {
+    thread::available_parallelism()
+        .map(|count| count.get())
+        .unwrap_or(1)
+}
+
+fn extract_one<T: BpmOcrDebugOutputter>(
+    extractor: &BloodPressureReadingExtractor,
+    input: &BatchInput,
+    debugger: &Arc<T>,
+) -> Result<BloodPressureReading, ProcessingError> {
+    let debugging_session = DebuggerTrace::new(&input.unique_trace_name, Arc::clone(debugger));
+
+    let image = match &input.source {
+        ImageSource::File(path) => {
+            let filename = path.to_str().ok_or(ProcessingError::AppError(
+                ReadingIdentificationError::InternalError("Image path is not valid unicode"),
+            ))?;
+
+            read_image_file(filename)?
+        }
+        ImageSource::Buffer(file_contents) => decode_image_buffer(file_contents)?,
+    };
+
+    extractor.extract(&image, &debugging_session)
+}
+
+/// Attempts to extract a blood pressure reading from every input, spreading the work over a fixed
+/// number of worker threads. The results are returned in the same order as the inputs.
+/// * `extractor` - the extractor to share between the workers
+/// * `inputs` - the images to extract readings from
+/// * `debugger` - the debugger to output debug images with, under each input's own trace name
+/// * `workers` - the maximum number of images to process at once
+pub fn extract_batch<T: BpmOcrDebugOutputter + Send + Sync>(
+    extractor: &BloodPressureReadingExtractor,
+    inputs: Vec<BatchInput>,
+    debugger: Arc<T>,
+    workers: usize,
+) -> Vec<Result<BloodPressureReading, ProcessingError>> {
+    let next_input = AtomicUsize::new(0);
+    let worker_count = workers.clamp(1, inputs.len().max(1));
+
+    let worker_results: Vec<(usize, Result<BloodPressureReading, ProcessingError>)> =
+        thread::scope(|scope| {
+            let handles: Vec<_> = (0..worker_count)
+                .map(|_| {
+                    let inputs = &inputs;
+                    let next_input = &next_input;
+                    let debugger = &debugger;
+
+                    scope.spawn(move || {
+                        let mut worker_results = Vec::new();
+
+                        loop {
+                            let index = next_input.fetch_add(1, Ordering::Relaxed);
+
+                            let Some(input) = inputs.get(index) else {
+                                break;
+                            };
+
+                            // A panic while reading one image fails that image rather than the whole batch
+                            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                                extract_one(extractor, input, debugger)
+                            }))
+                            .unwrap_or(Err(
+                                ProcessingError::AppError(
+                                    ReadingIdentificationError::InternalError(
+                                        "Extraction panicked while reading the image",
+                                    ),
+                                ),
+                            ));
+
+                            worker_results.push((index, result));
+                        }
+
+                        worker_results
+                    })
+                })
+                .collect();
+
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().unwrap_or_default())
+                .collect()
+        });
+
+    let mut results: Vec<Option<Result<BloodPressureReading, ProcessingError>>> =
+        inputs.iter().map(|_| None).collect();
+
+    for (index, result) in worker_results {
+        results[index] = Some(result);
+    }
+
+    // Only left empty if a worker stopped outside of reading an image
+    results
+        .into_iter()
+        .map(|result| {
+            result.unwrap_or(Err(ProcessingError::AppError(
+                ReadingIdentificationError::InternalError(
+                    "Extraction worker stopped before reading the image",
+                ),
+            )))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::debug::TempFolderDebugger;
+    use opencv::core::Mat;
+
+    /// Panics on the first debug image of the trace named `panics`
+    struct PanickingDebugger {}
+
+    impl BpmOcrDebugOutputter for PanickingDebugger {
+        fn new(_: bool) -> Self {
+            PanickingDebugger {}
+        }
+
+        fn output(&self, unique_trace_id: &str, _: &Mat, _: &str) -> Result<(), ProcessingError> {
+            assert_ne!(unique_trace_id, "panics");
+            Ok(())
+        }
+
+        fn debug_enabled(&self) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn test_batch_results_in_input_order() {
+        let inputs = vec![
+            BatchInput::from_buffer(
+                "test_batch_at_angle",
+                Vec::from(include_bytes!("./test_resources/example_at_angle.jpg")),
+            ),
+            BatchInput::from_buffer(
+                "test_batch_top_down",
+                Vec::from(include_bytes!("./test_resources/example_top_down.jpg")),
+            ),
+            BatchInput::from_buffer(
+                "test_batch_contour_candidates",
+                Vec::from(include_bytes!("./test_resources/contour_candidates.jpeg")),
+            ),
+        ];
+
+        let results = extract_batch(
+            &BloodPressureReadingExtractor::default(),
+            inputs,
+            Arc::new(TempFolderDebugger::new(true)),
+            2,
+        );
+
+        let readings: Vec<BloodPressureReading> =
+            results.into_iter().map(|result| result.unwrap()).collect();
+
+        assert_eq!(
+            readings,
+            vec![
+                BloodPressureReading {
+                    systolic: 133,
+                    diastolic: 93,
+                    pulse: 65,
+                },
+                BloodPressureReading {
+                    systolic: 131,
+                    diastolic: 88,
+                    pulse: 77,
+                },
+                BloodPressureReading {
+                    systolic: 123,
+                    diastolic: 85,
+                    pulse: 68,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_panic_fails_only_its_image() {
+        let inputs = vec![
+            BatchInput::from_buffer(
+                "panics",
+                Vec::from(include_bytes!("./test_resources/example_at_angle.jpg")),
+            ),
+            BatchInput::from_buffer(
+                "test_batch_after_panic",
+                Vec::from(include_bytes!("./test_resources/example_top_down.jpg")),
+            ),
+        ];
+
+        let results = extract_batch(
+            &BloodPressureReadingExtractor::default(),
+            inputs,
+            Arc::new(PanickingDebugger {}),
+            1,
+        );
+
+        assert!(matches!(
+            results[0],
+            Err(ProcessingError::AppError(
+                ReadingIdentificationError::InternalError(_)
+            ))
+        ));
+        assert_eq!(
+            results[1].as_ref().unwrap(),
+            &BloodPressureReading {
+                systolic: 131,
+                diastolic: 88,
+                pulse: 77,
+            }
+        );
+    }
+
+    #[test]
+    fn test_trace_names_unique_per_path() {
+        assert_eq!(
+            trace_name_for_path(Path::new("morning/reading.jpg")),
+            "morning_reading.jpg"
+        );
+        assert_ne!(
+            trace_name_for_path(Path::new("morning/reading.jpg")),
+            trace_name_for_path(Path::new("evening/reading.jpg"))
+        );
+        assert_eq!(
+            trace_name_for_path(Path::new("/photos/reading.jpg")),
+            "photos_reading.jpg"
+        );
+        assert_ne!(
+            trace_name_for_path(Path::new("a_b/c.jpg")),
+            trace_name_for_path(Path::new("a/b_c.jpg"))
+        );
+    }
+}
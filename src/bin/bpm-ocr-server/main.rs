@@ -0,0 +1,272 @@
+use std::{net::SocketAddr, path::PathBuf, process::ExitCode, sync::Arc};
+
+use axum::{
+    Json, Router,
+    body::Bytes,
+    extract::{DefaultBodyLimit, FromRequest, Multipart, Request, State},
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+};
+use bpm_ocr::{
+    batch::default_worker_count,
+    config::ExtractorConfig,
+    get_reading_report_from_buffer,
+    models::{
+        BloodPressureReading, DebuggerTrace, DeviceDateTime, DisplayedReading, IndicatorFlags,
+        ProcessingError, ReadingIdentificationError,
+    },
+};
+use clap::Parser;
+use serde::Serialize;
+use tokio::{net::TcpListener, sync::Semaphore};
+
+#[path = "../common/config_file.rs"]
+mod config_file;
+
+/// Serves blood pressure monitor readings from photos over HTTP
+#[derive(Parser)]
+#[command(name = "bpm-ocr-server", version)]
+struct Cli {
+    /// The address to listen on
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    listen: SocketAddr,
+
+    /// The largest photo, in bytes, that will be accepted
+    #[arg(long, default_value_t = 10 * 1024 * 1024)]
+    max_body_bytes: usize,
+
+    /// How many photos to read at once. Defaults to one per core
+    #[arg(long)]
+    max_concurrent_readings: Option<usize>,
+
+    /// A JSON file of pipeline tuning parameters. Any that are left out take their default values
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// The name of a built in monitor profile describing where the monitor shows each field, or
+    /// `detect` to pick from the built in profiles by the layout of the digits. Overrides any
+    /// profile in the config file
+    #[arg(long)]
+    profile: Option<String>,
+
+    /// The name of a built in font describing how the monitor draws its digits, e.g. `all_styles`
+    /// for a monitor that draws 6, 7 or 9 without tails. Overrides any font in the config file
+    #[arg(long)]
+    font: Option<String>,
+}
+
+struct AppState {
+    config: ExtractorConfig,
+    readings_in_progress: Semaphore,
+}
+
+#[derive(Serialize)]
+struct HealthResponse {
+    status: &'static str,
+}
+
+#[derive(Serialize)]
+struct ReadingResponse {
+    reading: BloodPressureReading,
+    /// The reading in the unit the monitor showed it in
+    displayed: DisplayedReading,
+    indicators: IndicatorFlags,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    date_time: Option<DeviceDateTime>,
+}
+
+#[derive(Serialize)]
+struct ErrorResponse {
+    error: ErrorBody,
+}
+
+#[derive(Serialize)]
+struct ErrorBody {
+    kind: &'static str,
+    message: String,
+    /// The error or status code the monitor showed, if it showed one instead of a reading
+    #[serde(skip_serializing_if = "Option::is_none")]
+    device_code: Option<String>,
+}
+
+fn error_response(status: StatusCode, kind: &'static str, message: String) -> Response {
+    let body = ErrorResponse {
+        error: ErrorBody {
+            kind,
+            message,
+            device_code: None,
+        },
+    };
+
+    (status, Json(body)).into_response()
+}
+
+fn processing_error_response(error: ProcessingError) -> Response {
+    // Most failures are down to the photo rather than the server, so the caller can retry with a better photo
+    let status = match &error {
+        ProcessingError::AppError(_) => StatusCode::UNPROCESSABLE_ENTITY,
+        ProcessingError::ImageDetectionLibraryError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+
+    let device_code = match &error {
+        ProcessingError::AppError(ReadingIdentificationError::DeviceReportedError { code }) => {
+            Some(code.clone())
+        }
+        _ => None,
+    };
+
+    let body = ErrorResponse {
+        error: ErrorBody {
+            kind: error.name(),
+            message: error.to_string(),
+            device_code,
+        },
+    };
+
+    (status, Json(body)).into_response()
+}
+
+async fn health() -> Json<HealthResponse> {
+    Json(HealthResponse { status: "ok" })
+}
+
+/// Takes the photo from either the raw request body or, for multipart uploads, the `image` field
+/// or the first file
+async fn image_from_request(state: &Arc<AppState>, request: Request) -> Result<Bytes, Response> {
+    let is_multipart = request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .map(|content_type| content_type.starts_with("multipart/form-data"))
+        .unwrap_or(false);
+
+    if !is_multipart {
+        return Bytes::from_request(request, state)
+            .await
+            .map_err(IntoResponse::into_response);
+    }
+
+    let mut multipart = Multipart::from_request(request, state)
+        .await
+        .map_err(IntoResponse::into_response)?;
+
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(IntoResponse::into_response)?
+    {
+        if field.name() == Some("image") || field.file_name().is_some() {
+            return field.bytes().await.map_err(IntoResponse::into_response);
+        }
+    }
+
+    Err(error_response(
+        StatusCode::BAD_REQUEST,
+        "missing_image",
+        "The multipart upload has no `image` field or file".to_owned(),
+    ))
+}
+
+async fn create_reading(State(state): State<Arc<AppState>>, request: Request) -> Response {
+    let image = match image_from_request(&state, request).await {
+        Ok(image) => image,
+        Err(response) => return response,
+    };
+
+    if image.is_empty() {
+        return error_response(
+            StatusCode::BAD_REQUEST,
+            "missing_image",
+            "The request has no photo in it".to_owned(),
+        );
+    }
+
+    let Ok(_permit) = state.readings_in_progress.acquire().await else {
+        return error_response(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "shutting_down",
+            "The server is no longer accepting photos".to_owned(),
+        );
+    };
+
+    let config = state.config.clone();
+
+    let result = tokio::task::spawn_blocking(move || {
+        get_reading_report_from_buffer(image.to_vec(), DebuggerTrace::no_debug_session(), config)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(report)) => {
+            let response = ReadingResponse {
+                reading: report.reading.reading(),
+                displayed: report.reading.displayed(),
+                indicators: report.indicators,
+                date_time: report.date_time,
+            };
+
+            (StatusCode::OK, Json(response)).into_response()
+        }
+        Ok(Err(error)) => processing_error_response(error),
+        Err(_) => error_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "internal_error",
+            "The photo could not be read".to_owned(),
+        ),
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    let config = match config_file::load_config(
+        cli.config.as_deref(),
+        cli.profile.as_deref(),
+        cli.font.as_deref(),
+    ) {
+        Ok(config) => config,
+        Err(error) => {
+            eprintln!("bpm-ocr-server: {}", error);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let max_concurrent_readings = cli
+        .max_concurrent_readings
+        .unwrap_or_else(default_worker_count)
+        .max(1);
+
+    let state = Arc::new(AppState {
+        config,
+        readings_in_progress: Semaphore::new(max_concurrent_readings),
+    });
+
+    let app = Router::new()
+        .route("/health", get(health))
+        .route("/readings", post(create_reading))
+        .layer(DefaultBodyLimit::max(cli.max_body_bytes))
+        .with_state(state);
+
+    let listener = match TcpListener::bind(cli.listen).await {
+        Ok(listener) => listener,
+        Err(error) => {
+            eprintln!(
+                "bpm-ocr-server: could not listen on {}: {}",
+                cli.listen, error
+            );
+            return ExitCode::FAILURE;
+        }
+    };
+
+    eprintln!("bpm-ocr-server: listening on {}", cli.listen);
+
+    match axum::serve(listener, app).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("bpm-ocr-server: {}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,270 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::Arc,
+};
+
+use bpm_ocr::{
+    BloodPressureReadingExtractor,
+    batch::{BatchInput, ImageSource, default_worker_count, extract_batch, trace_name_for_path},
+    config::ExtractorConfig,
+    debug::{BpmOcrDebugOutputter, DirectoryDebugger, NoDebug},
+    models::{BloodPressureReading, ProcessingError},
+};
+use clap::{Args, ValueEnum};
+use serde::Serialize;
+
+use crate::CommonArgs;
+
+const IMAGE_EXTENSIONS: [&str; 7] = ["jpg", "jpeg", "png", "bmp", "webp", "tif", "tiff"];
+
+#[derive(Args)]
+pub struct BatchArgs {
+    /// The directory of photos to read
+    directory: PathBuf,
+
+    /// The format of the report
+    #[arg(long, value_enum, default_value_t = ReportFormat::Csv)]
+    format: ReportFormat,
+
+    /// Write the report to this file instead of stdout
+    #[arg(long, short)]
+    output: Option<PathBuf>,
+
+    /// Also read photos in sub directories
+    #[arg(long)]
+    recursive: bool,
+
+    /// How many photos to read at once. Defaults to one per core
+    #[arg(long)]
+    workers: Option<usize>,
+
+    #[command(flatten)]
+    common: CommonArgs,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ReportFormat {
+    /// A header row followed by one row per photo
+    Csv,
+    /// One JSON object per photo, one per line
+    Jsonl,
+}
+
+#[derive(Serialize)]
+struct ReportRow<'a> {
+    file: &'a str,
+    systolic: Option<i32>,
+    diastolic: Option<i32>,
+    pulse: Option<i32>,
+    error: Option<&'static str>,
+}
+
+impl<'a> ReportRow<'a> {
+    fn new(file: &'a str, result: &Result<BloodPressureReading, ProcessingError>) -> Self {
+        let reading = result.as_ref().ok();
+
+        ReportRow {
+            file,
+            systolic: reading.map(|reading| reading.systolic),
+            diastolic: reading.map(|reading| reading.diastolic),
+            pulse: reading.map(|reading| reading.pulse),
+            error: result.as_ref().err().map(|error| error.name()),
+        }
+    }
+}
+
+fn is_image(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| IMAGE_EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+fn find_images(directory: &Path, recursive: bool, images: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+
+        if path.is_dir() {
+            if recursive {
+                find_images(&path, recursive, images)?;
+            }
+        } else if is_image(&path) {
+            images.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+pub fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_owned()
+    }
+}
+
+/// Splits a row of a CSV file into its fields, undoing any quoting like `csv_field` adds. Nothing is
+/// returned if a quoted field is never closed.
+pub fn split_csv_row(row: &str) -> Option<Vec<String>> {
+    let mut fields: Vec<String> = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = row.chars().peekable();
+
+    while let Some(char) = chars.next() {
+        match char {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' if in_quotes => in_quotes = false,
+            '"' if field.trim().is_empty() => {
+                field.clear();
+                in_quotes = true;
+            }
+            ',' if !in_quotes => fields.push(std::mem::take(&mut field)),
+            char => field.push(char),
+        }
+    }
+
+    if in_quotes {
+        return None;
+    }
+
+    fields.push(field);
+
+    Some(fields)
+}
+
+fn optional_field<T: ToString>(value: Option<T>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+fn write_report(
+    output: &mut dyn Write,
+    format: ReportFormat,
+    files: &[String],
+    results: &[Result<BloodPressureReading, ProcessingError>],
+) -> io::Result<()> {
+    if let ReportFormat::Csv = format {
+        writeln!(output, "file,systolic,diastolic,pulse,error")?;
+    }
+
+    for (file, result) in files.iter().zip(results) {
+        let row = ReportRow::new(file, result);
+
+        match format {
+            ReportFormat::Csv => writeln!(
+                output,
+                "{},{},{},{},{}",
+                csv_field(row.file),
+                optional_field(row.systolic),
+                optional_field(row.diastolic),
+                optional_field(row.pulse),
+                optional_field(row.error)
+            )?,
+            ReportFormat::Jsonl => {
+                let line = serde_json::to_string(&row).map_err(io::Error::other)?;
+                writeln!(output, "{}", line)?
+            }
+        }
+    }
+
+    output.flush()
+}
+
+fn print_summary(results: &[Result<BloodPressureReading, ProcessingError>]) {
+    let mut failures_by_kind: BTreeMap<&'static str, usize> = BTreeMap::new();
+
+    for error in results.iter().filter_map(|result| result.as_ref().err()) {
+        *failures_by_kind.entry(error.name()).or_default() += 1;
+    }
+
+    let failed: usize = failures_by_kind.values().sum();
+
+    eprintln!(
+        "read {} of {} photos, {} failed",
+        results.len() - failed,
+        results.len(),
+        failed
+    );
+
+    for (kind, count) in failures_by_kind {
+        eprintln!("  {}: {}", kind, count);
+    }
+}
+
+fn run_with_debugger<T: BpmOcrDebugOutputter + Send + Sync>(
+    args: &BatchArgs,
+    config: ExtractorConfig,
+    debugger: Arc<T>,
+) -> Result<(), String> {
+    let mut images = Vec::new();
+    find_images(&args.directory, args.recursive, &mut images)
+        .map_err(|error| format!("could not list {}: {}", args.directory.display(), error))?;
+    images.sort();
+
+    let files: Vec<String> = images
+        .iter()
+        .map(|path| {
+            path.strip_prefix(&args.directory)
+                .unwrap_or(path)
+                .display()
+                .to_string()
+        })
+        .collect();
+
+    let inputs: Vec<BatchInput> = images
+        .into_iter()
+        .zip(&files)
+        .map(|(path, file)| BatchInput {
+            unique_trace_name: trace_name_for_path(Path::new(file)),
+            source: ImageSource::File(path),
+        })
+        .collect();
+
+    let workers = args.workers.unwrap_or_else(default_worker_count);
+    let extractor =
+        BloodPressureReadingExtractor::try_new(config).map_err(|error| error.to_string())?;
+    let results = extract_batch(&extractor, inputs, debugger, workers);
+
+    let mut output: Box<dyn Write> = match &args.output {
+        Some(path) => {
+            Box::new(BufWriter::new(File::create(path).map_err(|error| {
+                format!("could not create {}: {}", path.display(), error)
+            })?))
+        }
+        None => Box::new(io::stdout().lock()),
+    };
+
+    write_report(&mut output, args.format, &files, &results)
+        .map_err(|error| format!("could not write the report: {}", error))?;
+
+    print_summary(&results);
+
+    Ok(())
+}
+
+pub fn run(args: BatchArgs) -> ExitCode {
+    let result = crate::load_config(&args.common).and_then(|config| match &args.common.debug_dir {
+        Some(directory) => run_with_debugger(
+            &args,
+            config,
+            Arc::new(DirectoryDebugger::in_directory(directory.clone())),
+        ),
+        None => run_with_debugger(&args, config, Arc::new(NoDebug {})),
+    });
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("bpm-ocr: {}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
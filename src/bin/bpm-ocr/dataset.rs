@@ -0,0 +1,173 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::Arc,
+};
+
+use bpm_ocr::{
+    BloodPressureReadingExtractor,
+    batch::trace_name_for_path,
+    dataset::{DigitCropEntry, LabelledPhoto, export_digit_crops},
+    debug::{BpmOcrDebugOutputter, DirectoryDebugger, NoDebug},
+    models::{BloodPressureReading, DebuggerTrace},
+};
+use clap::Args;
+
+use crate::{
+    CommonArgs,
+    batch::{csv_field, split_csv_row},
+};
+
+#[derive(Args)]
+pub struct DatasetArgs {
+    /// A CSV file with a `file,systolic,diastolic,pulse` header and one row per photo giving the
+    /// reading the photo is known to show, in mmHg. Paths are relative to the CSV file, and may be quoted
+    labels: PathBuf,
+
+    /// The directory to write the digit crops and their `manifest.csv` into
+    output: PathBuf,
+
+    #[command(flatten)]
+    common: CommonArgs,
+}
+
+fn read_labels(path: &Path) -> Result<Vec<LabelledPhoto>, String> {
+    let contents = fs::read_to_string(path)
+        .map_err(|error| format!("could not read {}: {}", path.display(), error))?;
+
+    let directory = path.parent().unwrap_or(Path::new(""));
+
+    contents
+        .lines()
+        .enumerate()
+        .skip(1)
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            let invalid_row =
+                || format!("{} line {} is not a valid row", path.display(), index + 1);
+
+            let columns = split_csv_row(line).ok_or_else(invalid_row)?;
+
+            let [file, systolic, diastolic, pulse] = columns.as_slice() else {
+                return Err(invalid_row());
+            };
+
+            let parse = |value: &str| value.trim().parse::<i32>().map_err(|_| invalid_row());
+
+            Ok(LabelledPhoto {
+                source: directory.join(file.trim()),
+                name: trace_name_for_path(&Path::new(file.trim()).with_extension("")),
+                reading: BloodPressureReading {
+                    systolic: parse(systolic)?,
+                    diastolic: parse(diastolic)?,
+                    pulse: parse(pulse)?,
+                },
+            })
+        })
+        .collect()
+}
+
+fn write_manifest(output: &mut dyn Write, entries: &[DigitCropEntry]) -> io::Result<()> {
+    writeln!(
+        output,
+        "crop_file,source,profile_name,field,digit_index,digit,x,y,width,height"
+    )?;
+
+    for entry in entries {
+        writeln!(
+            output,
+            "{},{},{},{},{},{},{},{},{},{}",
+            csv_field(&entry.crop_file),
+            csv_field(&entry.source),
+            csv_field(&entry.profile_name),
+            entry.field,
+            entry.digit_index,
+            entry.digit,
+            entry.x,
+            entry.y,
+            entry.width,
+            entry.height
+        )?;
+    }
+
+    output.flush()
+}
+
+fn run_with_debugger<T: BpmOcrDebugOutputter>(
+    args: &DatasetArgs,
+    extractor: &BloodPressureReadingExtractor,
+    debugger: Arc<T>,
+) -> Result<(), String> {
+    let photos = read_labels(&args.labels)?;
+
+    fs::create_dir_all(&args.output)
+        .map_err(|error| format!("could not create {}: {}", args.output.display(), error))?;
+
+    let mut entries: Vec<DigitCropEntry> = Vec::new();
+    let mut failures_by_kind: BTreeMap<&'static str, usize> = BTreeMap::new();
+
+    for photo in &photos {
+        let trace_name = trace_name_for_path(&photo.source);
+        let debugging_session = DebuggerTrace::new(&trace_name, Arc::clone(&debugger));
+
+        match export_digit_crops(extractor, photo, &args.output, &debugging_session) {
+            Ok(photo_entries) => entries.extend(photo_entries),
+            Err(error) => {
+                eprintln!("{}: {}", photo.source.display(), error);
+                *failures_by_kind.entry(error.name()).or_default() += 1;
+            }
+        }
+    }
+
+    let manifest_path = args.output.join("manifest.csv");
+    let mut manifest = BufWriter::new(
+        File::create(&manifest_path)
+            .map_err(|error| format!("could not create {}: {}", manifest_path.display(), error))?,
+    );
+
+    write_manifest(&mut manifest, &entries)
+        .map_err(|error| format!("could not write the manifest: {}", error))?;
+
+    let failed: usize = failures_by_kind.values().sum();
+
+    eprintln!(
+        "wrote {} digits from {} of {} photos, {} failed",
+        entries.len(),
+        photos.len() - failed,
+        photos.len(),
+        failed
+    );
+
+    for (kind, count) in failures_by_kind {
+        eprintln!("  {}: {}", kind, count);
+    }
+
+    Ok(())
+}
+
+pub fn run(args: DatasetArgs) -> ExitCode {
+    let result = crate::load_config(&args.common).and_then(|config| {
+        let extractor =
+            BloodPressureReadingExtractor::try_new(config).map_err(|error| error.to_string())?;
+
+        match &args.common.debug_dir {
+            Some(directory) => run_with_debugger(
+                &args,
+                &extractor,
+                Arc::new(DirectoryDebugger::in_directory(directory.clone())),
+            ),
+            None => run_with_debugger(&args, &extractor, Arc::new(NoDebug {})),
+        }
+    });
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("bpm-ocr: {}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,314 @@
+use std::{
+    io::{self, Read},
+    path::PathBuf,
+    process::ExitCode,
+    sync::Arc,
+};
+
+use bpm_ocr::{
+    batch::trace_name_for_path,
+    config::ExtractorConfig,
+    debug::{BpmOcrDebugOutputter, DirectoryDebugger, NoDebug},
+    get_reading_report_from_buffer, get_reading_report_from_file,
+    models::{
+        BloodPressureReading, DebuggerTrace, DeviceDateTime, DisplayedReading, IndicatorFlags,
+        PressureUnit, ProcessingError, ReadingReport,
+    },
+};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use serde::Serialize;
+
+mod batch;
+#[path = "../common/config_file.rs"]
+mod config_file;
+mod dataset;
+
+/// Reads the blood pressure and pulse from photos of blood pressure monitor screens
+#[derive(Parser)]
+#[command(name = "bpm-ocr", version, args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    read: ReadArgs,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Read every photo in a directory and write a CSV or JSONL report of the results
+    Batch(batch::BatchArgs),
+    /// Write a crop of every digit in photos whose readings are already known, labelled with the
+    /// digit it shows, to build a training and evaluation set from
+    Dataset(dataset::DatasetArgs),
+}
+
+#[derive(Args)]
+struct ReadArgs {
+    /// Photos to read. Reads a single photo from stdin if none are given or the path is `-`
+    images: Vec<PathBuf>,
+
+    /// How to print each reading
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
+
+    #[command(flatten)]
+    common: CommonArgs,
+}
+
+#[derive(Args)]
+pub struct CommonArgs {
+    /// Write the intermediate images of each stage of the pipeline into this directory, one folder per photo
+    #[arg(long)]
+    pub debug_dir: Option<PathBuf>,
+
+    /// A JSON file of pipeline tuning parameters. Any that are left out take their default values
+    #[arg(long)]
+    pub config: Option<PathBuf>,
+
+    /// The name of a built in monitor profile describing where the monitor shows each field, e.g.
+    /// `pulse_beside_diastolic`, or `detect` to pick from the built in profiles by the layout of the
+    /// digits. Overrides any profile in the config file
+    #[arg(long)]
+    pub profile: Option<String>,
+
+    /// The name of a built in font describing how the monitor draws its digits, e.g. `all_styles`
+    /// for a monitor that draws 6, 7 or 9 without tails. Overrides any font in the config file
+    #[arg(long)]
+    pub font: Option<String>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum OutputFormat {
+    /// One human readable line per photo
+    Text,
+    /// One JSON object per photo, one per line
+    Json,
+}
+
+enum Input {
+    File(PathBuf),
+    Stdin,
+}
+
+impl Input {
+    fn name(&self) -> String {
+        match self {
+            Input::File(path) => path.display().to_string(),
+            Input::Stdin => "stdin".to_owned(),
+        }
+    }
+
+    fn trace_name(&self) -> String {
+        match self {
+            Input::File(path) => trace_name_for_path(path),
+            Input::Stdin => "stdin".to_owned(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonOutput<'a> {
+    source: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reading: Option<BloodPressureReading>,
+    /// The reading in the unit the monitor showed it in
+    #[serde(skip_serializing_if = "Option::is_none")]
+    displayed: Option<DisplayedReading>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    indicators: Option<&'a IndicatorFlags>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    date_time: Option<&'a DeviceDateTime>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+fn read_input<T: BpmOcrDebugOutputter>(
+    input: &Input,
+    debugger: &Arc<T>,
+    config: &ExtractorConfig,
+) -> Result<ReadingReport, String> {
+    let debugging_session = DebuggerTrace::new(&input.trace_name(), Arc::clone(debugger));
+
+    let result: Result<ReadingReport, ProcessingError> = match input {
+        Input::File(path) => {
+            let filename = path
+                .to_str()
+                .ok_or_else(|| "the path is not valid unicode".to_owned())?;
+
+            get_reading_report_from_file(filename, debugging_session, config.clone())
+        }
+        Input::Stdin => {
+            let mut contents = Vec::new();
+            io::stdin()
+                .read_to_end(&mut contents)
+                .map_err(|error| format!("could not read stdin: {}", error))?;
+
+            get_reading_report_from_buffer(contents, debugging_session, config.clone())
+        }
+    };
+
+    result.map_err(|error| error.to_string())
+}
+
+fn describe_report(report: &ReadingReport) -> String {
+    let reading = report.reading.reading();
+    let displayed = report.reading.displayed();
+
+    let mut description = match displayed.unit {
+        PressureUnit::MmHg => format!(
+            "{}/{} mmHg, pulse {}",
+            reading.systolic, reading.diastolic, reading.pulse
+        ),
+        PressureUnit::KPa => format!(
+            "{}/{} kPa ({}/{} mmHg), pulse {}",
+            displayed.systolic,
+            displayed.diastolic,
+            reading.systolic,
+            reading.diastolic,
+            reading.pulse
+        ),
+    };
+
+    if let Some(date_time) = &report.date_time {
+        if let (Some(month), Some(day)) = (date_time.month, date_time.day) {
+            description.push_str(&format!(
+                ", taken {:02}-{:02} {:02}:{:02}",
+                month, day, date_time.hour, date_time.minute
+            ));
+        } else {
+            description.push_str(&format!(
+                ", taken at {:02}:{:02}",
+                date_time.hour, date_time.minute
+            ));
+        }
+    }
+
+    let lit_indicators: Vec<String> = report
+        .indicators
+        .lit()
+        .iter()
+        .map(|indicator| indicator.to_string())
+        .collect();
+
+    if !lit_indicators.is_empty() {
+        description.push_str(&format!(" ({})", lit_indicators.join(", ")));
+    }
+
+    description
+}
+
+fn print_result(input: &Input, result: &Result<ReadingReport, String>, format: OutputFormat) {
+    let name = input.name();
+
+    match format {
+        OutputFormat::Text => match result {
+            Ok(report) => println!("{}: {}", name, describe_report(report)),
+            Err(error) => eprintln!("{}: could not read the monitor: {}", name, error),
+        },
+        OutputFormat::Json => {
+            let report = result.as_ref().ok();
+
+            let output = JsonOutput {
+                source: &name,
+                reading: report.map(|report| report.reading.reading()),
+                displayed: report.map(|report| report.reading.displayed()),
+                indicators: report.map(|report| &report.indicators),
+                date_time: report.and_then(|report| report.date_time.as_ref()),
+                error: result.as_ref().err().cloned(),
+            };
+
+            match serde_json::to_string(&output) {
+                Ok(line) => println!("{}", line),
+                Err(error) => eprintln!("{}: could not format the result: {}", name, error),
+            }
+        }
+    }
+}
+
+fn run<T: BpmOcrDebugOutputter>(
+    inputs: &[Input],
+    debugger: Arc<T>,
+    config: &ExtractorConfig,
+    format: OutputFormat,
+) -> ExitCode {
+    let mut all_succeeded = true;
+
+    for input in inputs {
+        let result = read_input(input, &debugger, config);
+        all_succeeded &= result.is_ok();
+
+        print_result(input, &result, format);
+    }
+
+    if all_succeeded {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+pub fn load_config(common: &CommonArgs) -> Result<ExtractorConfig, String> {
+    config_file::load_config(
+        common.config.as_deref(),
+        common.profile.as_deref(),
+        common.font.as_deref(),
+    )
+}
+
+fn read(args: ReadArgs) -> ExitCode {
+    let config = match load_config(&args.common) {
+        Ok(config) => config,
+        Err(error) => {
+            eprintln!("bpm-ocr: {}", error);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    // Stdin can only be read once
+    let stdin_count = args
+        .images
+        .iter()
+        .filter(|path| path.as_os_str() == "-")
+        .count();
+
+    if stdin_count > 1 {
+        eprintln!("bpm-ocr: `-` can only be given once, as stdin can only be read once");
+        return ExitCode::FAILURE;
+    }
+
+    let inputs: Vec<Input> = if args.images.is_empty() {
+        vec![Input::Stdin]
+    } else {
+        args.images
+            .into_iter()
+            .map(|path| {
+                if path.as_os_str() == "-" {
+                    Input::Stdin
+                } else {
+                    Input::File(path)
+                }
+            })
+            .collect()
+    };
+
+    match args.common.debug_dir {
+        Some(directory) => run(
+            &inputs,
+            Arc::new(DirectoryDebugger::in_directory(directory)),
+            &config,
+            args.format,
+        ),
+        None => run(&inputs, Arc::new(NoDebug {}), &config, args.format),
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Some(Command::Batch(args)) => batch::run(args),
+        Some(Command::Dataset(args)) => dataset::run(args),
+        None => read(cli.read),
+    }
+}
@@ -0,0 +1,71 @@
+use std::{fs, path::Path};
+
+use bpm_ocr::{
+    config::ExtractorConfig,
+    font::SegmentFont,
+    profile::{MonitorProfile, ProfileSelection},
+};
+
+/// Loads the tuning parameters from a JSON config file, then overrides its profile and font with
+/// the named built in ones. The error describes what was wrong, for printing to the user.
+/// * `path` - the config file. Every parameter takes its default value if there isn't one
+/// * `profile` - the name of a built in monitor profile to always read the screen with, or `detect`
+/// * `font` - the name of a built in font to read the digits with
+pub fn load_config(
+    path: Option<&Path>,
+    profile: Option<&str>,
+    font: Option<&str>,
+) -> Result<ExtractorConfig, String> {
+    let mut config = match path {
+        Some(path) => {
+            let contents = fs::read_to_string(path)
+                .map_err(|error| format!("could not read {}: {}", path.display(), error))?;
+
+            let config: ExtractorConfig = serde_json::from_str(&contents)
+                .map_err(|error| format!("could not parse {}: {}", path.display(), error))?;
+
+            config
+                .validate()
+                .map_err(|message| format!("invalid config in {}: {}", path.display(), message))?;
+
+            config
+        }
+        None => ExtractorConfig::default(),
+    };
+
+    if profile == Some("detect") {
+        config.profile = ProfileSelection::detect_built_in();
+    } else if let Some(name) = profile {
+        let profile = MonitorProfile::built_in(name).ok_or_else(|| {
+            let known_profiles: Vec<String> = MonitorProfile::built_in_profiles()
+                .into_iter()
+                .map(|profile| profile.name)
+                .collect();
+
+            format!(
+                "unknown profile {}, expected detect or one of {}",
+                name,
+                known_profiles.join(", ")
+            )
+        })?;
+
+        config.profile = ProfileSelection::Fixed(profile);
+    }
+
+    if let Some(name) = font {
+        config.segments.font = SegmentFont::built_in(name).ok_or_else(|| {
+            let known_fonts: Vec<String> = SegmentFont::built_in_fonts()
+                .into_iter()
+                .map(|font| font.name)
+                .collect();
+
+            format!(
+                "unknown font {}, expected one of {}",
+                name,
+                known_fonts.join(", ")
+            )
+        })?;
+    }
+
+    Ok(config)
+}
@@ -0,0 +1,336 @@
+use std::sync::Mutex;
+
+use opencv::{
+    core::{
+        AlgorithmTraitConst, CV_32F, Mat, MatTraitConst, MatTraitConstManual, Ptr, Rect2i, Size,
+    },
+    imgproc,
+    ml::{self, KNearest, KNearestTrait, KNearestTraitConst, StatModelTrait, StatModelTraitConst},
+};
+
+use crate::models::{DigitConfidence, ProcessingError, ReadingIdentificationError};
+
+/// Every digit is scaled to this size before it is compared, so that digits of any size can be classified
+const FEATURE_WIDTH: i32 = 12;
+const FEATURE_HEIGHT: i32 = 24;
+
+/// The fraction of the nearest examples that must agree on a digit for it to be read, unless set otherwise
+const DEFAULT_MIN_AGREEMENT: f32 = 0.6;
+
+/// How a trained classifier is combined with the segment rules when reading digits
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ClassifierMode {
+    /// Every digit is read by the classifier alone. Letters in device codes are still read by
+    /// their segments.
+    Replace,
+    /// Digits are read by their segments, and only those whose segments do not match a digit are
+    /// read by the classifier
+    Fallback,
+}
+
+/// A crop of a single digit from a highlighted LCD screen, as produced by
+/// `SevenSegmentReader::highlight`, along with the digit it shows
+pub struct LabelledDigit {
+    pub image: Mat,
+    pub digit: i32,
+}
+
+/// Reads digits by comparing them with the nearest of a set of labelled examples, for fonts whose
+/// segments do not sit where the segment rules expect them to be
+pub struct DigitClassifier {
+    // OpenCV models cannot be shared between threads without a lock
+    model: Mutex<Ptr<KNearest>>,
+    min_agreement: f32,
+}
+
+impl DigitClassifier {
+    /// Trains a classifier that reads each digit as the most common digit among its `k` most
+    /// similar examples
+    pub fn train(examples: &[LabelledDigit], k: i32) -> Result<Self, ProcessingError> {
+        if examples.is_empty() {
+            return Err(ProcessingError::AppError(
+                ReadingIdentificationError::InvalidClassifier(
+                    "At least one labelled digit is needed to train the classifier",
+                ),
+            ));
+        }
+
+        if k < 1 {
+            return Err(ProcessingError::AppError(
+                ReadingIdentificationError::InvalidClassifier(
+                    "The classifier must compare against at least one example",
+                ),
+            ));
+        }
+
+        if examples
+            .iter()
+            .any(|example| !(0..=9).contains(&example.digit))
+        {
+            return Err(ProcessingError::AppError(
+                ReadingIdentificationError::InvalidClassifier(
+                    "Every labelled digit must be from 0 to 9",
+                ),
+            ));
+        }
+
+        let mut features: Vec<f32> = Vec::new();
+        let mut labels: Vec<f32> = Vec::with_capacity(examples.len());
+
+        for example in examples {
+            let whole_image = Rect2i::new(0, 0, example.image.cols(), example.image.rows());
+
+            features.extend(digit_features(&example.image, whole_image)?);
+            labels.push(example.digit as f32);
+        }
+
+        let samples = Mat::from_slice(&features)?
+            .reshape(1, examples.len() as i32)?
+            .try_clone()?;
+        let responses = Mat::from_slice(&labels)?
+            .reshape(1, examples.len() as i32)?
+            .try_clone()?;
+
+        let mut model = KNearest::create()?;
+        model.set_default_k(k)?;
+        model.set_is_classifier(true)?;
+        model.train(&samples, ml::ROW_SAMPLE, &responses)?;
+
+        Ok(DigitClassifier {
+            model: Mutex::new(model),
+            min_agreement: DEFAULT_MIN_AGREEMENT,
+        })
+    }
+
+    /// Loads a classifier saved with `save`
+    pub fn load(path: &str) -> Result<Self, ProcessingError> {
+        let model = KNearest::load(path)?;
+
+        if !model.is_trained()? {
+            return Err(ProcessingError::AppError(
+                ReadingIdentificationError::InvalidClassifier(
+                    "The file does not contain a trained classifier",
+                ),
+            ));
+        }
+
+        Ok(DigitClassifier {
+            model: Mutex::new(model),
+            min_agreement: DEFAULT_MIN_AGREEMENT,
+        })
+    }
+
+    /// Sets the fraction of the nearest examples that must agree on a digit for it to be read. A
+    /// digit with less agreement than this is left unrecognised.
+    pub fn with_min_agreement(mut self, min_agreement: f32) -> Self {
+        self.min_agreement = min_agreement;
+        self
+    }
+
+    pub fn min_agreement(self: &Self) -> f32 {
+        self.min_agreement
+    }
+
+    /// Saves the classifier, along with all of the examples it was trained with, to a file
+    pub fn save(self: &Self, path: &str) -> Result<(), ProcessingError> {
+        self.lock()?.save(path)?;
+
+        Ok(())
+    }
+
+    /// Reads the digit at the given location of a highlighted LCD screen. The confidence is the
+    /// fraction of the nearest examples that agree on the digit.
+    pub fn classify(
+        self: &Self,
+        image: &Mat,
+        location: Rect2i,
+    ) -> Result<DigitConfidence, ProcessingError> {
+        let features = digit_features(image, location)?;
+        let sample = Mat::from_slice(&features)?.try_clone()?;
+
+        let model = self.lock()?;
+        let k = model.get_default_k()?;
+
+        let mut results = Mat::default();
+        let mut neighbour_responses = Mat::default();
+        let mut distances = Mat::default();
+
+        model.find_nearest(
+            &sample,
+            k,
+            &mut results,
+            &mut neighbour_responses,
+            &mut distances,
+        )?;
+
+        let digit = *results.at_2d::<f32>(0, 0)? as i32;
+
+        let mut votes = [0; 10];
+        for neighbour in neighbour_responses.data_typed::<f32>()? {
+            if let Some(count) = votes.get_mut(*neighbour as usize) {
+                *count += 1;
+            }
+        }
+
+        let runner_up = (0..10)
+            .filter(|other| *other != digit)
+            .max_by_key(|other| votes[*other as usize])
+            .filter(|other| votes[*other as usize] > 0);
+
+        let best_votes = votes.get(digit as usize).copied().unwrap_or(0);
+        let runner_up_votes = runner_up.map(|other| votes[other as usize]).unwrap_or(0);
+
+        Ok(DigitConfidence {
+            digit,
+            confidence: best_votes as f32 / k as f32,
+            runner_up,
+            margin: (best_votes - runner_up_votes) as f32 / k as f32,
+        })
+    }
+
+    /// Reads the digit at the given location of a highlighted LCD screen, or nothing if too few of
+    /// the nearest examples agree on it
+    pub fn classify_if_agreed(
+        self: &Self,
+        image: &Mat,
+        location: Rect2i,
+    ) -> Result<Option<DigitConfidence>, ProcessingError> {
+        let digit = self.classify(image, location)?;
+
+        Ok((digit.confidence >= self.min_agreement).then_some(digit))
+    }
+
+    fn lock(self: &Self) -> Result<std::sync::MutexGuard<'_, Ptr<KNearest>>, ProcessingError> {
+        self.model.lock().map_err(|_| {
+            ProcessingError::AppError(ReadingIdentificationError::InternalError(
+                "Internal error: the classifier was poisoned by a panic while in use",
+            ))
+        })
+    }
+}
+
+/// The brightness of each pixel of the digit once scaled to a fixed size, from 0.0 to 1.0
+fn digit_features(image: &Mat, location: Rect2i) -> Result<Vec<f32>, ProcessingError> {
+    let digit = image.roi(location)?;
+
+    let mut resized = Mat::default();
+    imgproc::resize(
+        &digit,
+        &mut resized,
+        Size::new(FEATURE_WIDTH, FEATURE_HEIGHT),
+        0.,
+        0.,
+        imgproc::INTER_AREA,
+    )?;
+
+    let mut scaled = Mat::default();
+    resized.convert_to(&mut scaled, CV_32F, 1. / 255., 0.)?;
+
+    Ok(scaled.data_typed::<f32>()?.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::font::{SegmentFont, SymbolGlyph};
+    use crate::seven_segment::{DigitParseResult, SevenSegmentReader};
+    use crate::test_drawing::{draw_glyph, strokes};
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    /// Every digit of the standard font, drawn with the given stroke thickness, labelled with its digit
+    fn draw_labelled_digits(stroke: i32) -> Vec<LabelledDigit> {
+        SegmentFont::standard()
+            .glyphs
+            .iter()
+            .map(|glyph| {
+                let (image, location) = draw_glyph(strokes(glyph.segments, stroke));
+
+                LabelledDigit {
+                    image: image.roi(location).unwrap().try_clone().unwrap(),
+                    digit: glyph.digit,
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_classifier_trained_saved_and_loaded() {
+        let examples: Vec<LabelledDigit> = [draw_labelled_digits(10), draw_labelled_digits(7)]
+            .into_iter()
+            .flatten()
+            .collect();
+
+        let classifier = DigitClassifier::train(&examples, 3).unwrap();
+
+        let path = std::env::temp_dir().join(format!("bpm_ocr_classifier_{}.yml", Uuid::new_v4()));
+        let path = path.to_str().unwrap();
+        classifier.save(path).unwrap();
+
+        let loaded = DigitClassifier::load(path);
+        std::fs::remove_file(path).unwrap();
+
+        let reader = SevenSegmentReader::default()
+            .with_classifier(Arc::new(loaded.unwrap()), ClassifierMode::Replace);
+
+        // Digits drawn with a stroke none of the examples have
+        for held_out in draw_labelled_digits(8) {
+            let location = Rect2i::new(0, 0, held_out.image.cols(), held_out.image.rows());
+
+            match reader.read_digit(&held_out.image, location).unwrap() {
+                DigitParseResult::Digit(digit) => assert_eq!(digit.digit, held_out.digit),
+                result => panic!("{} was read as {:?}", held_out.digit, result),
+            }
+        }
+    }
+
+    #[test]
+    fn test_classifier_rejects_labels_that_are_not_digits() {
+        let mut examples = draw_labelled_digits(10);
+        examples[0].digit = -1;
+
+        assert!(matches!(
+            DigitClassifier::train(&examples, 1),
+            Err(ProcessingError::AppError(
+                ReadingIdentificationError::InvalidClassifier(_)
+            ))
+        ));
+
+        examples[0].digit = 10;
+
+        assert!(DigitClassifier::train(&examples, 1).is_err());
+    }
+
+    #[test]
+    fn test_classifier_leaves_digits_without_agreement_unrecognised() {
+        let examples: Vec<LabelledDigit> = [draw_labelled_digits(10), draw_labelled_digits(7)]
+            .into_iter()
+            .flatten()
+            .collect();
+
+        // Only two examples of each digit, so the third nearest example always disagrees
+        let classifier = DigitClassifier::train(&examples, 3)
+            .unwrap()
+            .with_min_agreement(1.0);
+
+        let reader = SevenSegmentReader::default()
+            .with_classifier(Arc::new(classifier), ClassifierMode::Replace);
+
+        let (eight, location) = draw_glyph([8; 7]);
+
+        assert!(matches!(
+            reader.read_digit(&eight, location).unwrap(),
+            DigitParseResult::UnrecognisedSegments(_)
+        ));
+
+        // Letters are still read by their segments
+        let (letter_e, location) = draw_glyph(strokes(
+            SymbolGlyph::new('E', [1, 1, 0, 1, 1, 0, 1]).segments,
+            10,
+        ));
+        assert!(matches!(
+            reader.read_character(&letter_e, location).unwrap(),
+            DigitParseResult::Symbol { symbol: 'E', .. }
+        ));
+    }
+}
@@ -0,0 +1,441 @@
+use opencv::imgproc;
+use serde::{Deserialize, Serialize};
+
+use crate::{font::SegmentFont, profile::ProfileSelection};
+
+/// Tuning parameters for every stage of the extraction pipeline. The defaults are tuned against the
+/// test photos. They are not the pixel values the pipeline originally hard-coded, as thresholds are
+/// now fractions of the LCD screen's size and photos keep their aspect ratio when resized.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExtractorConfig {
+    pub normalization: NormalizationConfig,
+    pub lcd_screen: LcdScreenConfig,
+    pub digits: DigitLocationConfig,
+    pub segments: SegmentConfig,
+    pub validation: ValidationConfig,
+    /// Where the monitor shows each field of the reading on its LCD screen. By default every screen
+    /// is read with the `stacked` profile.
+    pub profile: ProfileSelection,
+}
+
+impl ExtractorConfig {
+    /// Checks every tuning parameter is within the range the pipeline can work with, returning what
+    /// is wrong with the first one that is not. The profile and font are checked on their own.
+    pub fn validate(&self) -> Result<(), &'static str> {
+        self.normalization.validate()?;
+        self.lcd_screen.validate()?;
+        self.digits.validate()?;
+        self.segments.validate()?;
+        self.validation.validate()
+    }
+}
+
+/// Whether the value is a fraction from 0.0 to 1.0
+fn is_fraction<T: PartialOrd + From<u8>>(value: T) -> bool {
+    T::from(0) <= value && value <= T::from(1)
+}
+
+/// Controls how the input photo is resized before any processing happens. The photo keeps its
+/// aspect ratio so that the shape of the LCD screen is not distorted.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NormalizationConfig {
+    /// The length in pixels the longest edge of the photo is resized to
+    pub long_edge: i32,
+    /// An OpenCV interpolation flag, e.g. `imgproc::INTER_AREA`
+    pub interpolation: i32,
+}
+
+/// Controls how the LCD screen is located within the resized photo.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LcdScreenConfig {
+    /// Width and height of the gaussian blur kernel. Must be odd.
+    pub blur_kernel_size: i32,
+    pub canny_low_threshold: f64,
+    pub canny_high_threshold: f64,
+    /// The maximum distance between a contour and its approximated polygon, as a fraction of the
+    /// contour's perimeter
+    pub polygon_approximation_epsilon: f64,
+    pub candidate_scoring: LcdCandidateScoringConfig,
+}
+
+/// Controls how the quadrilaterals found in the photo are ranked by how likely they are to be the LCD screen.
+/// Each candidate scores 1.0 for every measure that falls within its expected range, falling away towards 0.0
+/// the further outside of the range it is. The scores are multiplied together.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LcdCandidateScoringConfig {
+    /// The expected range of the screen's width divided by its height
+    pub min_aspect_ratio: f64,
+    pub max_aspect_ratio: f64,
+    /// The expected range of the fraction of the photo taken up by the screen
+    pub min_image_fraction: f64,
+    pub max_image_fraction: f64,
+    /// The standard deviation of pixel brightness within the candidate at which it scores 1.0 for contrast
+    pub target_contrast: f64,
+    /// Candidates scoring lower than this are not read at all
+    pub min_score: f64,
+}
+
+/// Controls how individual digits are located on the perspective corrected LCD screen. Distances
+/// are given as fractions of the height of the LCD screen so that they hold however large the
+/// screen appears in the photo.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DigitLocationConfig {
+    /// Width and height of the dilation kernel used to join up the segments of each digit
+    pub dilation_kernel_size: i32,
+    /// Contours this height or shorter are not considered to be digits
+    pub min_digit_height_ratio: f32,
+    /// Digits whose top edges are closer together than this are considered to be on the same row
+    pub row_grouping_ratio: f32,
+    /// Shapes no bigger than this fraction of the height of the digits either side of them, sitting
+    /// at the bottom of the gap between them, are decimal points
+    pub max_decimal_point_size_ratio: f32,
+    /// Neighbouring digits further apart than this fraction of their height belong to different
+    /// numbers. Only used when reading a display without a monitor profile.
+    pub number_gap_ratio: f32,
+}
+
+/// Controls how the segments of a digit are decided to be lit up.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SegmentConfig {
+    /// The fraction of a segment's area that must be filled in for it to count as lit up
+    pub lit_threshold: f32,
+    /// A digit box filled in by more than this fraction may be a 1
+    pub one_fill_ratio: f32,
+    /// A digit box narrower than this width to height ratio may be a 1
+    pub one_max_width_to_height_ratio: f32,
+    /// How gradually a segment goes from unlit to lit as its fill ratio passes the lit threshold.
+    /// Larger values let a faint segment count for more when deciding which digit is shown.
+    pub lit_softness: f32,
+    /// The most segments that may disagree with the closest digit for it to still be recognised
+    pub max_mismatched_segments: usize,
+    /// How the display draws each digit. Defaults to the standard font.
+    pub font: SegmentFont,
+    /// How each digit is sampled to decide which of its segments are lit up, unless the monitor
+    /// profile says otherwise
+    pub sampling: SegmentSampling,
+}
+
+/// How a digit's box is sampled to decide which of its segments are lit up
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SegmentSampling {
+    /// How much of a fixed zone of the box for each segment is filled in. Suits fonts with thick
+    /// strokes.
+    Zones(ZoneSampling),
+    /// How many of the scanlines cast through the box cross a stroke where each segment should be.
+    /// Suits thin or slanted fonts, whose strokes do not fill the fixed zones.
+    Scanlines(ScanlineSampling),
+}
+
+impl SegmentSampling {
+    pub fn validate(&self) -> Result<(), &'static str> {
+        match self {
+            SegmentSampling::Zones(zones) => {
+                let is_size = |ratio: f32| 0. < ratio && ratio <= 1.;
+
+                if !is_size(zones.side_width_ratio)
+                    || !is_size(zones.end_height_ratio)
+                    || !is_size(zones.centre_height_ratio)
+                {
+                    return Err("The segment zones must be above 0 and no bigger than the digit");
+                }
+            }
+            SegmentSampling::Scanlines(scanlines) => {
+                if scanlines.count < 1 {
+                    return Err("At least one scanline must be cast through each segment");
+                }
+
+                if !is_fraction(scanlines.spread) {
+                    return Err("The spread of the scanlines must be from 0.0 to 1.0");
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Zones of the default size
+    pub fn zones() -> Self {
+        SegmentSampling::Zones(ZoneSampling::default())
+    }
+
+    /// The default number and spread of scanlines
+    pub fn scanlines() -> Self {
+        SegmentSampling::Scanlines(ScanlineSampling::default())
+    }
+}
+
+impl Default for SegmentSampling {
+    fn default() -> Self {
+        SegmentSampling::zones()
+    }
+}
+
+/// The size of the zone each segment is sampled from, as fractions of the digit's box
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ZoneSampling {
+    /// The width of the zones down the left and right of the box
+    pub side_width_ratio: f32,
+    /// The height of the zones across the top and bottom of the box
+    pub end_height_ratio: f32,
+    /// The height of the zone across the middle of the box
+    pub centre_height_ratio: f32,
+}
+
+/// Where the scanlines are cast through each segment of the digit's box
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScanlineSampling {
+    /// How many scanlines are cast through each segment. Each segment's fill ratio is the fraction
+    /// of them that cross a stroke, so more scanlines give a finer grained confidence.
+    pub count: usize,
+    /// How far apart the outermost scanlines are, as a fraction of the width of the box for the
+    /// vertical scanlines and of half its height for the horizontal ones. Several are spread out
+    /// so that one gap in a stroke does not lose a segment.
+    pub spread: f32,
+}
+
+impl ScanlineSampling {
+    /// Where each scanline is cast, as an offset from the middle of the part of the box it crosses
+    pub fn offsets(&self) -> Vec<f32> {
+        if self.count <= 1 {
+            return vec![0.; self.count];
+        }
+
+        (0..self.count)
+            .map(|index| self.spread * (index as f32 / (self.count - 1) as f32 - 0.5))
+            .collect()
+    }
+}
+
+impl NormalizationConfig {
+    fn validate(&self) -> Result<(), &'static str> {
+        if self.long_edge < 1 {
+            return Err("The photo must be resized to at least one pixel");
+        }
+
+        Ok(())
+    }
+}
+
+impl LcdScreenConfig {
+    fn validate(&self) -> Result<(), &'static str> {
+        if self.blur_kernel_size < 1 || self.blur_kernel_size % 2 == 0 {
+            return Err("The blur kernel size must be a positive odd number");
+        }
+
+        if self.canny_low_threshold < 0. || self.canny_low_threshold > self.canny_high_threshold {
+            return Err("The low Canny threshold must be from 0 up to the high threshold");
+        }
+
+        if self.polygon_approximation_epsilon <= 0. {
+            return Err("The polygon approximation epsilon must be above 0");
+        }
+
+        self.candidate_scoring.validate()
+    }
+}
+
+impl LcdCandidateScoringConfig {
+    fn validate(&self) -> Result<(), &'static str> {
+        if self.min_aspect_ratio <= 0. || self.min_aspect_ratio > self.max_aspect_ratio {
+            return Err("The minimum aspect ratio must be above 0 and no more than the maximum");
+        }
+
+        if !is_fraction(self.min_image_fraction)
+            || !is_fraction(self.max_image_fraction)
+            || self.min_image_fraction > self.max_image_fraction
+        {
+            return Err(
+                "The image fractions must be from 0.0 to 1.0, with the minimum no more than the maximum",
+            );
+        }
+
+        if self.target_contrast <= 0. {
+            return Err("The target contrast must be above 0");
+        }
+
+        if !is_fraction(self.min_score) {
+            return Err("The minimum candidate score must be from 0.0 to 1.0");
+        }
+
+        Ok(())
+    }
+}
+
+impl DigitLocationConfig {
+    fn validate(&self) -> Result<(), &'static str> {
+        if self.dilation_kernel_size < 1 {
+            return Err("The dilation kernel size must be at least 1");
+        }
+
+        if !is_fraction(self.min_digit_height_ratio)
+            || !is_fraction(self.max_decimal_point_size_ratio)
+        {
+            return Err(
+                "The minimum digit height and maximum decimal point size ratios must be from 0.0 to 1.0",
+            );
+        }
+
+        if self.row_grouping_ratio < 0. || self.number_gap_ratio < 0. {
+            return Err("The row grouping and number gap ratios must not be negative");
+        }
+
+        Ok(())
+    }
+}
+
+impl SegmentConfig {
+    fn validate(&self) -> Result<(), &'static str> {
+        if !is_fraction(self.lit_threshold) || !is_fraction(self.one_fill_ratio) {
+            return Err("The lit threshold and fill ratio of a 1 must be from 0.0 to 1.0");
+        }
+
+        if self.one_max_width_to_height_ratio <= 0. {
+            return Err("The width to height ratio of a 1 must be above 0");
+        }
+
+        if self.lit_softness <= 0. {
+            return Err("The lit softness must be above 0");
+        }
+
+        self.sampling.validate()
+    }
+}
+
+impl Default for NormalizationConfig {
+    fn default() -> Self {
+        NormalizationConfig {
+            long_edge: 800,
+            interpolation: imgproc::INTER_AREA,
+        }
+    }
+}
+
+impl Default for LcdScreenConfig {
+    fn default() -> Self {
+        LcdScreenConfig {
+            blur_kernel_size: 5,
+            canny_low_threshold: 50.,
+            canny_high_threshold: 200.,
+            polygon_approximation_epsilon: 0.02,
+            candidate_scoring: LcdCandidateScoringConfig::default(),
+        }
+    }
+}
+
+impl Default for LcdCandidateScoringConfig {
+    fn default() -> Self {
+        LcdCandidateScoringConfig {
+            min_aspect_ratio: 0.5,
+            max_aspect_ratio: 2.0,
+            min_image_fraction: 0.02,
+            max_image_fraction: 0.9,
+            target_contrast: 40.,
+            min_score: 0.01,
+        }
+    }
+}
+
+impl Default for DigitLocationConfig {
+    fn default() -> Self {
+        DigitLocationConfig {
+            dilation_kernel_size: 3,
+            min_digit_height_ratio: 0.08,
+            row_grouping_ratio: 0.015,
+            max_decimal_point_size_ratio: 0.3,
+            number_gap_ratio: 0.5,
+        }
+    }
+}
+
+impl Default for ZoneSampling {
+    fn default() -> Self {
+        ZoneSampling {
+            side_width_ratio: 0.25,
+            end_height_ratio: 0.15,
+            centre_height_ratio: 0.1,
+        }
+    }
+}
+
+impl Default for ScanlineSampling {
+    fn default() -> Self {
+        ScanlineSampling {
+            count: 3,
+            spread: 0.3,
+        }
+    }
+}
+
+impl Default for SegmentConfig {
+    fn default() -> Self {
+        SegmentConfig {
+            lit_threshold: 0.55,
+            one_fill_ratio: 0.77,
+            one_max_width_to_height_ratio: 0.30,
+            lit_softness: 0.1,
+            max_mismatched_segments: 1,
+            font: SegmentFont::default(),
+            sampling: SegmentSampling::default(),
+        }
+    }
+}
+
+/// An inclusive range of values a field of a reading is expected to fall within
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ValueRange {
+    pub min: i32,
+    pub max: i32,
+}
+
+impl ValueRange {
+    pub fn contains(&self, value: i32) -> bool {
+        self.min <= value && value <= self.max
+    }
+}
+
+/// Controls which readings are considered physiologically plausible. Readings that are not are
+/// assumed to have been misread, e.g. because a digit was missed.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ValidationConfig {
+    pub enabled: bool,
+    pub systolic: ValueRange,
+    pub diastolic: ValueRange,
+    pub pulse: ValueRange,
+    /// The smallest allowed difference between the systolic and diastolic pressure. A reading
+    /// where the diastolic pressure is not below the systolic pressure is always rejected.
+    pub min_pulse_pressure: i32,
+}
+
+impl ValidationConfig {
+    fn validate(&self) -> Result<(), &'static str> {
+        let ranges = [self.systolic, self.diastolic, self.pulse];
+
+        if ranges.iter().any(|range| range.min > range.max) {
+            return Err("The minimum of each plausible range must be no more than its maximum");
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for ValidationConfig {
+    fn default() -> Self {
+        ValidationConfig {
+            enabled: true,
+            systolic: ValueRange { min: 60, max: 260 },
+            diastolic: ValueRange { min: 30, max: 160 },
+            pulse: ValueRange { min: 30, max: 220 },
+            min_pulse_pressure: 10,
+        }
+    }
+}
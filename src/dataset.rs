@@ -0,0 +1,287 @@
+use std::path::{Path, PathBuf};
+
+use opencv::{
+    core::{MatTraitConst, Rect2i, Vector},
+    imgcodecs::{self, ImreadModes},
+};
+use serde::Serialize;
+
+use crate::{
+    BloodPressureReadingExtractor,
+    classifier::LabelledDigit,
+    debug::BpmOcrDebugOutputter,
+    models::{
+        BloodPressureReading, DebuggerTrace, PressureUnit, ProcessingError, ReadingField,
+        ReadingIdentificationError, ReadingLocations,
+    },
+    read_image_file,
+};
+
+/// A photo of a monitor along with the reading it is known to show, e.g. from someone checking it by eye
+pub struct LabelledPhoto {
+    pub source: PathBuf,
+    /// What the photo's crops are named after. Must be unique among the photos exported into the
+    /// same dataset directory, e.g. the photo's path relative to its labels file.
+    pub name: String,
+    /// The reading exactly as the screen shows it. Only whole mmHg readings can be labelled, so
+    /// photos of screens showing kPa are rejected.
+    pub reading: BloodPressureReading,
+}
+
+/// A digit crop written to the dataset, and where it came from
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct DigitCropEntry {
+    /// The crop's file name within the dataset directory
+    pub crop_file: String,
+    pub source: String,
+    pub profile_name: String,
+    pub field: String,
+    /// The position of the digit within its field, counting from the left
+    pub digit_index: usize,
+    pub digit: i32,
+    /// Where the digit is on the perspective corrected LCD screen
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+/// Finds the digits of the photo's reading and writes a crop of each one, from the highlighted LCD
+/// screen, into the dataset directory named after the digit it shows. The digits only have to be
+/// found rather than read, so photos the segment rules get wrong still make it into the dataset.
+/// The config, profile and font are validated the same as when extracting a reading.
+/// * `extractor` - the extractor whose configuration the digits are found with
+/// * `photo` - the photo and the reading it is known to show
+/// * `dataset_directory` - the existing directory to write the crops into
+/// * `debugging_session` - the debugger trace session to output debug images for the photo with
+pub fn export_digit_crops<T: BpmOcrDebugOutputter>(
+    extractor: &BloodPressureReadingExtractor,
+    photo: &LabelledPhoto,
+    dataset_directory: &Path,
+    debugging_session: &DebuggerTrace<T>,
+) -> Result<Vec<DigitCropEntry>, ProcessingError> {
+    let filename = photo.source.to_str().ok_or(ProcessingError::AppError(
+        ReadingIdentificationError::InternalError("Image path is not valid unicode"),
+    ))?;
+
+    let image = read_image_file(filename)?;
+
+    let fields = [
+        (ReadingField::Systolic, photo.reading.systolic),
+        (ReadingField::Diastolic, photo.reading.diastolic),
+        (ReadingField::Pulse, photo.reading.pulse),
+    ];
+
+    // The screen is the best ranked candidate with as many digits in each field as the known reading
+    let (highlighted_digits, locations) =
+        extractor.locate_digits(&image, debugging_session, |locations| {
+            fields.iter().all(|(field, value)| {
+                field_region(locations, *field).len() == value.to_string().len()
+            })
+        })?;
+
+    // A kPa reading has a decimal place the whole number label can't give the digit of
+    let located_unit = extractor
+        .config()
+        .profile
+        .profiles()
+        .iter()
+        .find(|profile| profile.name == locations.profile_name)
+        .map(|profile| profile.pressure_unit);
+
+    if let Some(PressureUnit::KPa) = located_unit {
+        return Err(ProcessingError::AppError(
+            ReadingIdentificationError::InvalidMonitorProfile(
+                "Digit crops can only be exported from profiles showing the reading in mmHg",
+            ),
+        ));
+    }
+
+    let mut entries: Vec<DigitCropEntry> = Vec::new();
+
+    for (field, value) in fields {
+        let digits = value
+            .to_string()
+            .chars()
+            .filter_map(|digit| digit.to_digit(10))
+            .collect::<Vec<u32>>();
+
+        for (digit_index, (location, digit)) in field_region(&locations, field)
+            .iter()
+            .zip(digits)
+            .enumerate()
+        {
+            let crop_file = format!("{}_{}_{}_{}.png", photo.name, field, digit_index, digit);
+            let crop = highlighted_digits.roi(*location)?;
+
+            let written = imgcodecs::imwrite(
+                &dataset_directory.join(&crop_file).to_string_lossy(),
+                &crop,
+                &Vector::new(),
+            )?;
+
+            if !written {
+                return Err(ProcessingError::AppError(
+                    ReadingIdentificationError::InternalError(
+                        "The digit crop could not be written",
+                    ),
+                ));
+            }
+
+            entries.push(DigitCropEntry {
+                crop_file,
+                source: photo.source.display().to_string(),
+                profile_name: locations.profile_name.clone(),
+                field: field.to_string(),
+                digit_index,
+                digit: digit as i32,
+                x: location.x,
+                y: location.y,
+                width: location.width,
+                height: location.height,
+            });
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Reads the crops written by `export_digit_crops` back in, e.g. to train a `DigitClassifier` with
+pub fn read_labelled_digits(
+    dataset_directory: &Path,
+    entries: &[DigitCropEntry],
+) -> Result<Vec<LabelledDigit>, ProcessingError> {
+    entries
+        .iter()
+        .map(|entry| {
+            let image = imgcodecs::imread(
+                &dataset_directory.join(&entry.crop_file).to_string_lossy(),
+                ImreadModes::IMREAD_GRAYSCALE.into(),
+            )?;
+
+            if image.empty() {
+                return Err(ProcessingError::AppError(
+                    ReadingIdentificationError::UnsupportedImageFormat,
+                ));
+            }
+
+            Ok(LabelledDigit {
+                image,
+                digit: entry.digit,
+            })
+        })
+        .collect()
+}
+
+fn field_region(locations: &ReadingLocations, field: ReadingField) -> &Vec<Rect2i> {
+    match field {
+        ReadingField::Systolic => &locations.systolic_region,
+        ReadingField::Diastolic => &locations.diastolic_region,
+        ReadingField::Pulse => &locations.pulse_region,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ExtractorConfig;
+    use crate::debug::TempFolderDebugger;
+    use crate::profile::{MonitorProfile, ProfileSelection};
+    use uuid::Uuid;
+
+    fn top_down_photo() -> LabelledPhoto {
+        LabelledPhoto {
+            source: concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/src/test_resources/example_top_down.jpg"
+            )
+            .into(),
+            name: "top_down_example".to_owned(),
+            reading: BloodPressureReading {
+                systolic: 131,
+                diastolic: 88,
+                pulse: 77,
+            },
+        }
+    }
+
+    #[test]
+    fn test_dataset_export_labels_each_digit() {
+        let debug_session: DebuggerTrace<TempFolderDebugger> =
+            DebuggerTrace::temp_folder_session("test_dataset_export_labels_each_digit");
+
+        let photo = top_down_photo();
+
+        let dataset_directory =
+            std::env::temp_dir().join(format!("bpm_ocr_dataset_{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dataset_directory).unwrap();
+
+        let entries = export_digit_crops(
+            &BloodPressureReadingExtractor::default(),
+            &photo,
+            &dataset_directory,
+            &debug_session,
+        )
+        .unwrap();
+
+        let digits: Vec<i32> = read_labelled_digits(&dataset_directory, &entries)
+            .unwrap()
+            .iter()
+            .map(|labelled_digit| labelled_digit.digit)
+            .collect();
+
+        std::fs::remove_dir_all(&dataset_directory).unwrap();
+
+        assert_eq!(digits, vec![1, 3, 1, 8, 8, 7, 7]);
+        assert!(entries.iter().all(|entry| {
+            entry.source.ends_with("example_top_down.jpg")
+                && entry.crop_file.starts_with("top_down_example_")
+        }));
+    }
+
+    #[test]
+    fn test_dataset_export_rejects_kpa_profiles() {
+        let debug_session: DebuggerTrace<TempFolderDebugger> =
+            DebuggerTrace::temp_folder_session("test_dataset_export_rejects_kpa_profiles");
+
+        let mut config = ExtractorConfig::default();
+        config.profile = ProfileSelection::Fixed(MonitorProfile::stacked_kpa());
+
+        let result = export_digit_crops(
+            &BloodPressureReadingExtractor::new(config),
+            &top_down_photo(),
+            &std::env::temp_dir(),
+            &debug_session,
+        );
+
+        assert!(matches!(
+            result,
+            Err(ProcessingError::AppError(
+                ReadingIdentificationError::InvalidMonitorProfile(_)
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_dataset_export_validates_config() {
+        let debug_session: DebuggerTrace<TempFolderDebugger> =
+            DebuggerTrace::temp_folder_session("test_dataset_export_validates_config");
+
+        let mut config = ExtractorConfig::default();
+        config.lcd_screen.blur_kernel_size = 4;
+
+        let result = export_digit_crops(
+            &BloodPressureReadingExtractor::new(config),
+            &top_down_photo(),
+            &std::env::temp_dir(),
+            &debug_session,
+        );
+
+        assert!(matches!(
+            result,
+            Err(ProcessingError::AppError(
+                ReadingIdentificationError::InvalidConfig(_)
+            ))
+        ));
+    }
+}
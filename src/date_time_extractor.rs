@@ -0,0 +1,297 @@
+use opencv::{
+    Error,
+    core::{Mat, MatTraitConst, Point, Rect2i, Vector},
+    imgproc::{self, bounding_rect, find_contours_def},
+};
+
+use crate::{
+    config::SegmentSampling,
+    models::{DeviceDateTime, ProcessingError},
+    profile::{DateOrder, DateTimeLayout},
+    seven_segment::{DigitParseResult, SevenSegmentReader},
+};
+
+/// Digits further apart than this fraction of their height are in different groups, e.g. the date and the hour
+const GROUP_GAP_RATIO: f32 = 0.5;
+
+/// Dots of a colon, and date separators such as a dash or full stop, are no bigger than this
+/// fraction of the height of the region
+const MAX_SEPARATOR_HEIGHT_RATIO: f32 = 0.35;
+
+/// A shape found within the date and time region of the LCD screen
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum DateTimeElement {
+    Digit(Rect2i),
+    Colon,
+    Separator,
+}
+
+/// Reads the date and time shown in the given region of the highlighted LCD screen. Nothing is
+/// returned if there is no time to be found or any of its digits could not be read. The digits'
+/// segments are sampled the same way as the rest of the screen's.
+pub fn read_date_time(
+    image: &Mat,
+    layout: &DateTimeLayout,
+    reader: &SevenSegmentReader,
+    sampling: SegmentSampling,
+) -> Result<Option<DeviceDateTime>, ProcessingError> {
+    let region = layout.region.to_rect(image.cols(), image.rows());
+
+    if region.area() == 0 {
+        return Ok(None);
+    }
+
+    let elements = find_elements(image, region, layout.min_digit_height_ratio)?;
+    let groups = group_digits(elements);
+
+    // The time is the only thing with a colon in it
+    let Some(colon_index) = groups.iter().position(|group| group.is_none()) else {
+        return Ok(None);
+    };
+
+    let hour_group = colon_index
+        .checked_sub(1)
+        .and_then(|index| groups.get(index));
+
+    let (Some(Some(hour_digits)), Some(Some(minute_digits))) =
+        (hour_group, groups.get(colon_index + 1))
+    else {
+        return Ok(None);
+    };
+
+    if hour_digits.len() > 2 || minute_digits.len() != 2 {
+        return Ok(None);
+    }
+
+    let (Some(hour), Some(minute)) = (
+        read_number(image, hour_digits, reader, sampling)?,
+        read_number(image, minute_digits, reader, sampling)?,
+    ) else {
+        return Ok(None);
+    };
+
+    if hour > 23 || minute > 59 {
+        return Ok(None);
+    }
+
+    let date_groups: Vec<&Vec<Rect2i>> = groups[..colon_index.saturating_sub(1)]
+        .iter()
+        .flatten()
+        .collect();
+
+    let date = match date_groups.as_slice() {
+        [first, second] if first.len() <= 2 && second.len() <= 2 => {
+            match (
+                read_number(image, first, reader, sampling)?,
+                read_number(image, second, reader, sampling)?,
+            ) {
+                (Some(first), Some(second)) => match layout.date_order {
+                    DateOrder::MonthDay => Some((first, second)),
+                    DateOrder::DayMonth => Some((second, first)),
+                },
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+    .filter(|(month, day)| (1..=12).contains(month) && (1..=31).contains(day));
+
+    Ok(Some(DeviceDateTime {
+        month: date.map(|(month, _)| month),
+        day: date.map(|(_, day)| day),
+        hour,
+        minute,
+    }))
+}
+
+/// Finds the digits, colons and separators within the region, from left to right
+fn find_elements(
+    image: &Mat,
+    region: Rect2i,
+    min_digit_height_ratio: f32,
+) -> Result<Vec<DateTimeElement>, ProcessingError> {
+    let focused_region = image.roi(region)?.try_clone()?;
+
+    let mut contours_output: Vector<Vector<Point>> = Vector::new();
+    find_contours_def(
+        &focused_region,
+        &mut contours_output,
+        imgproc::RETR_EXTERNAL,
+        imgproc::CHAIN_APPROX_SIMPLE,
+    )?;
+
+    let shapes: Vec<Rect2i> = contours_output
+        .into_iter()
+        .map(|contour| bounding_rect(&contour))
+        .collect::<Result<Vec<Rect2i>, Error>>()?;
+
+    Ok(classify_shapes(shapes, region, min_digit_height_ratio))
+}
+
+/// Decides which of the shapes found within the region, in the region's coordinates, are digits,
+/// colons and separators. The elements are given from left to right, with digits in the
+/// coordinates of the whole screen.
+fn classify_shapes(
+    mut shapes: Vec<Rect2i>,
+    region: Rect2i,
+    min_digit_height_ratio: f32,
+) -> Vec<DateTimeElement> {
+    shapes.sort_by(|shape1, shape2| shape1.x.cmp(&shape2.x));
+
+    let min_digit_height = (min_digit_height_ratio * region.height as f32) as i32;
+    let max_separator_height = (MAX_SEPARATOR_HEIGHT_RATIO * region.height as f32) as i32;
+
+    let mut elements: Vec<DateTimeElement> = Vec::new();
+    let mut index = 0;
+
+    while index < shapes.len() {
+        let shape = shapes[index];
+
+        if shape.height >= min_digit_height {
+            // Back into the coordinates of the whole screen so the digit can be read from it
+            elements.push(DateTimeElement::Digit(Rect2i::new(
+                shape.x + region.x,
+                shape.y + region.y,
+                shape.width,
+                shape.height,
+            )));
+        } else if shape.height <= max_separator_height {
+            // The two dots of a colon sit one above the other
+            let is_colon = shapes.get(index + 1).is_some_and(|next| {
+                next.height <= max_separator_height
+                    && next.x < shape.x + shape.width
+                    && (next.y - shape.y).abs() > shape.height
+            });
+
+            if is_colon {
+                elements.push(DateTimeElement::Colon);
+                index += 1;
+            } else {
+                elements.push(DateTimeElement::Separator);
+            }
+        }
+
+        index += 1;
+    }
+
+    elements
+}
+
+/// Groups neighbouring digits together, splitting them wherever there is a separator or a wide
+/// gap. Colons are kept as their own group of nothing so the time can be found around them.
+fn group_digits(elements: Vec<DateTimeElement>) -> Vec<Option<Vec<Rect2i>>> {
+    let mut groups: Vec<Option<Vec<Rect2i>>> = Vec::new();
+    let mut current: Vec<Rect2i> = Vec::new();
+
+    for element in elements {
+        match element {
+            DateTimeElement::Digit(digit) => {
+                let is_far_from_previous = current.last().is_some_and(|previous| {
+                    (digit.x - (previous.x + previous.width)) as f32
+                        > GROUP_GAP_RATIO * digit.height as f32
+                });
+
+                if is_far_from_previous {
+                    groups.push(Some(std::mem::take(&mut current)));
+                }
+
+                current.push(digit);
+            }
+            DateTimeElement::Colon => {
+                if !current.is_empty() {
+                    groups.push(Some(std::mem::take(&mut current)));
+                }
+
+                groups.push(None);
+            }
+            DateTimeElement::Separator => {
+                if !current.is_empty() {
+                    groups.push(Some(std::mem::take(&mut current)));
+                }
+            }
+        }
+    }
+
+    if !current.is_empty() {
+        groups.push(Some(current));
+    }
+
+    groups
+}
+
+fn read_number(
+    image: &Mat,
+    digits: &[Rect2i],
+    reader: &SevenSegmentReader,
+    sampling: SegmentSampling,
+) -> Result<Option<u32>, ProcessingError> {
+    let mut result: u32 = 0;
+
+    for digit in digits {
+        match reader.read_digit_sampled(image, *digit, sampling)? {
+            DigitParseResult::Digit(digit_confidence) => {
+                result = result * 10 + digit_confidence.digit as u32;
+            }
+            DigitParseResult::UnrecognisedSegments(_) | DigitParseResult::Symbol { .. } => {
+                return Ok(None);
+            }
+        }
+    }
+
+    Ok(Some(result))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_date_time_shapes_classified() {
+        let region = Rect2i::new(10, 5, 200, 40);
+
+        // Digits are at least half the height of the region, and separators at most 14 pixels tall
+        let shapes = vec![
+            Rect2i::new(60, 18, 8, 3),
+            Rect2i::new(0, 5, 10, 30),
+            Rect2i::new(30, 25, 4, 4),
+            Rect2i::new(30, 10, 4, 4),
+            Rect2i::new(80, 10, 10, 17),
+        ];
+
+        assert_eq!(
+            classify_shapes(shapes, region, 0.5),
+            vec![
+                DateTimeElement::Digit(Rect2i::new(10, 10, 10, 30)),
+                DateTimeElement::Colon,
+                DateTimeElement::Separator,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_date_time_digits_grouped() {
+        let digit = |x: i32| Rect2i::new(x, 0, 10, 20);
+
+        let elements = vec![
+            DateTimeElement::Digit(digit(0)),
+            DateTimeElement::Digit(digit(12)),
+            DateTimeElement::Digit(digit(40)),
+            DateTimeElement::Colon,
+            DateTimeElement::Digit(digit(60)),
+            DateTimeElement::Digit(digit(72)),
+            DateTimeElement::Separator,
+            DateTimeElement::Digit(digit(84)),
+        ];
+
+        assert_eq!(
+            group_digits(elements),
+            vec![
+                Some(vec![digit(0), digit(12)]),
+                Some(vec![digit(40)]),
+                None,
+                Some(vec![digit(60), digit(72)]),
+                Some(vec![digit(84)]),
+            ]
+        );
+    }
+}
@@ -1,4 +1,8 @@
-use std::{env, fs::create_dir_all};
+use std::{
+    env,
+    fs::create_dir_all,
+    path::{Path, PathBuf},
+};
 
 use opencv::{
     core::{AccessFlag, CV_8U, Mat, Point, Rect2i, Scalar, UMat, UMatTraitConst, Vector},
@@ -9,13 +13,19 @@ use opencv::{
 use crate::models;
 
 use models::{
-    LcdScreenCandidate, ProcessingError, ReadingIdentificationError, RejectedLcdScreenCandidate,
+    LcdCandidateRejection, LcdScreenCandidate, ProcessingError, ReadingIdentificationError,
+    RejectedLcdScreenCandidate,
 };
 
 pub struct TempFolderDebugger {
     debug_enabled: bool,
 }
 
+pub struct DirectoryDebugger {
+    debug_enabled: bool,
+    directory: PathBuf,
+}
+
 pub struct NoDebug {}
 
 pub trait BpmOcrDebugOutputter {
@@ -106,6 +116,42 @@ pub trait BpmOcrDebugOutputter {
         self.output(unique_trace_id, &colour, "contour_candidates")
     }
 
+    fn debug_rejected_lcd_candidate(
+        &self,
+        unique_trace_id: &str,
+        rank: usize,
+        image: &Mat,
+        candidate: &LcdScreenCandidate,
+        rejection: &LcdCandidateRejection,
+    ) -> Result<(), ProcessingError> {
+        if !self.debug_enabled() {
+            return Ok(());
+        }
+
+        let mut colour: Mat = Mat::default();
+
+        cvt_color(&image, &mut colour, COLOR_GRAY2RGB, 0)?;
+
+        let mut x: Vector<Vector<Point>> = Vector::new();
+        x.push(candidate.coordinates.clone());
+
+        draw_contours(
+            &mut colour,
+            &x,
+            0,
+            Scalar::new(0.0, 0.0, 255.0, 0.1),
+            2,
+            LINE_8.into(),
+            &Mat::default(),
+            i32::MAX,
+            Point::default(),
+        )?;
+
+        let stage_description = format!("rejected_lcd_candidate_{}_{}", rank, rejection.name());
+
+        self.output(unique_trace_id, &colour, &stage_description)
+    }
+
     fn debug_after_perspective_transform(
         &self,
         unique_trace_id: &str,
@@ -161,6 +207,59 @@ pub trait BpmOcrDebugOutputter {
 
         self.output(unique_trace_id, &temp_image, "digit_locations")
     }
+
+    /// Outlines where each indicator icon was looked for, in green if it was lit and red if not
+    fn debug_indicator_regions(
+        &self,
+        unique_trace_id: &str,
+        image: &Mat,
+        indicator_regions: &[(Rect2i, bool)],
+    ) -> Result<(), ProcessingError> {
+        if !self.debug_enabled() || indicator_regions.is_empty() {
+            return Ok(());
+        }
+
+        let mut temp_image = Mat::default();
+        cvt_color_def(&image, &mut temp_image, COLOR_GRAY2RGB)?;
+
+        for (region, lit) in indicator_regions {
+            let colour = if *lit {
+                Scalar::new(0.0, 255.0, 0.0, 0.0)
+            } else {
+                Scalar::new(0.0, 0.0, 255.0, 0.0)
+            };
+
+            rectangle_def(&mut temp_image, *region, colour)?;
+        }
+
+        self.output(unique_trace_id, &temp_image, "indicator_regions")
+    }
+}
+
+fn output_to_folder(
+    folder_path: &Path,
+    image: &Mat,
+    stage_description: &str,
+) -> Result<(), ProcessingError> {
+    // The folder is named after the trace, so it can only be created once there is something to output for it
+    create_dir_all(&folder_path).map_err(|_| {
+        ProcessingError::AppError(ReadingIdentificationError::InternalError(
+            "Could not create a folder for debugging image processing",
+        ))
+    })?;
+
+    let file_name = format!("{}.jpeg", &stage_description);
+
+    let binding = folder_path.join(file_name);
+
+    let file_path = binding.to_str().ok_or_else(|| {
+        ProcessingError::AppError(ReadingIdentificationError::InternalError(
+            &"Could not create a folder for debugging image processing",
+        ))
+    })?;
+
+    imwrite_def(&file_path, &image)?;
+    Ok(())
 }
 
 impl BpmOcrDebugOutputter for TempFolderDebugger {
@@ -178,25 +277,43 @@ impl BpmOcrDebugOutputter for TempFolderDebugger {
     ) -> Result<(), ProcessingError> {
         let folder_path = env::temp_dir().join("bpm-ocr").join(&unique_trace_id);
 
-        // TODO: create at construction
-        create_dir_all(&folder_path).map_err(|_| {
-            ProcessingError::AppError(ReadingIdentificationError::InternalError(
-                "Could not create a temporary folder for debugging image processing",
-            ))
-        })?;
+        output_to_folder(&folder_path, image, stage_description)
+    }
+
+    fn debug_enabled(&self) -> bool {
+        self.debug_enabled
+    }
+}
 
-        let file_name = format!("{}.jpeg", &stage_description);
+impl DirectoryDebugger {
+    /// Outputs debug images into a sub folder of the given directory for each trace
+    pub fn in_directory(directory: PathBuf) -> Self {
+        DirectoryDebugger {
+            debug_enabled: true,
+            directory,
+        }
+    }
+}
 
-        let binding = folder_path.join(file_name);
+impl BpmOcrDebugOutputter for DirectoryDebugger {
+    /// Outputs into the same folder as the `TempFolderDebugger`. Use `DirectoryDebugger::in_directory`
+    /// to choose the folder.
+    fn new(debug_enabled: bool) -> Self {
+        DirectoryDebugger {
+            debug_enabled,
+            directory: env::temp_dir().join("bpm-ocr"),
+        }
+    }
 
-        let file_path = binding.to_str().ok_or_else(|| {
-            ProcessingError::AppError(ReadingIdentificationError::InternalError(
-                &"Could not create a temporary folder for debugging image processing",
-            ))
-        })?;
+    fn output(
+        &self,
+        unique_trace_id: &str,
+        image: &Mat,
+        stage_description: &str,
+    ) -> Result<(), ProcessingError> {
+        let folder_path = self.directory.join(&unique_trace_id);
 
-        imwrite_def(&file_path, &image)?;
-        Ok(())
+        output_to_folder(&folder_path, image, stage_description)
     }
 
     fn debug_enabled(&self) -> bool {
@@ -1,25 +1,47 @@
 use opencv::{
     Error,
-    core::{Mat, MatTraitConst, Point, Rect2i, count_non_zero},
+    core::{Mat, MatTraitConst, MatTraitConstManual, Point, Rect2i, count_non_zero},
 };
 
-use crate::models::ProcessingError;
-
-static SEGMENTS_TO_NUMBER_MAP: [([i32; 7], i32); 10] = [
-    ([1, 1, 1, 0, 1, 1, 1], 0),
-    ([0, 0, 1, 0, 0, 1, 0], 1),
-    ([1, 0, 1, 1, 1, 0, 1], 2),
-    ([1, 0, 1, 1, 0, 1, 1], 3),
-    ([0, 1, 1, 1, 0, 1, 0], 4),
-    ([1, 1, 0, 1, 0, 1, 1], 5),
-    ([1, 1, 0, 1, 1, 1, 1], 6),
-    //([1, 0, 1, 0, 0, 1, 0], 7),
-    ([1, 1, 1, 0, 0, 1, 0], 7),
-    ([1, 1, 1, 1, 1, 1, 1], 8),
-    ([1, 1, 1, 1, 0, 1, 1], 9),
-];
-
-pub fn parse_digit(image: &Mat, full_digit_location: Rect2i) -> Result<i32, ProcessingError> {
+use crate::{
+    config::{ScanlineSampling, SegmentConfig, SegmentSampling, ZoneSampling},
+    models::{DigitConfidence, ProcessingError},
+};
+
+/// The outcome of matching the lit up segments of a digit against the known digits and symbols
+#[derive(Clone, Debug, PartialEq)]
+pub enum DigitParseResult {
+    Digit(DigitConfidence),
+    /// The segments exactly matched one of the font's letters or its dash rather than a digit. Only
+    /// given when reading a character that may be a letter, never when reading a digit.
+    Symbol {
+        symbol: char,
+        segments: [bool; 7],
+    },
+    /// The segments that were lit up, in the order top, top left, top right, centre, bottom left,
+    /// bottom right and bottom, did not match any digit
+    UnrecognisedSegments([bool; 7]),
+}
+
+/// How far a fill ratio is from the cut off that decides whether it is lit up, scaled so that 0.0 is
+/// right on the cut off and 1.0 is as far from it as a ratio can possibly be
+fn distance_from_cutoff(ratio: f32, cutoff: f32) -> f32 {
+    let distance = if ratio > cutoff {
+        (ratio - cutoff) / (1.0 - cutoff)
+    } else {
+        (cutoff - ratio) / cutoff
+    };
+
+    distance.clamp(0.0, 1.0)
+}
+
+/// How much of the box is filled in and how wide it is for its height, if it is narrow and filled
+/// in enough to be a 1. A 1 only lights up the segments down one side, so its box is just those.
+fn narrow_one_shape(
+    image: &Mat,
+    full_digit_location: Rect2i,
+    config: &SegmentConfig,
+) -> Result<Option<(f32, f32)>, ProcessingError> {
     let focused_digit = image.roi(full_digit_location)?;
     let total_filled_in_area = count_non_zero(&focused_digit)?;
     let total_area = full_digit_location.area();
@@ -27,14 +49,189 @@ pub fn parse_digit(image: &Mat, full_digit_location: Rect2i) -> Result<i32, Proc
     let width_to_height_ratio =
         full_digit_location.width as f32 / full_digit_location.height as f32;
 
+    let fill_ratio = (total_filled_in_area as f32) / (total_area as f32);
+
     // If we're drawn a box around an area that's mostly filled in and its a thin width, then it's probably a 1
-    if (total_filled_in_area as f32) / (total_area as f32) > 0.77 && width_to_height_ratio < 0.30 {
-        return Ok(1);
+    if fill_ratio > config.one_fill_ratio
+        && width_to_height_ratio < config.one_max_width_to_height_ratio
+    {
+        Ok(Some((fill_ratio, width_to_height_ratio)))
+    } else {
+        Ok(None)
+    }
+}
+
+/// How lit up each segment of the digit is, from 0.0 to 1.0, sampled the given way
+fn segment_fill_ratios(
+    image: &Mat,
+    full_digit_location: Rect2i,
+    sampling: SegmentSampling,
+) -> Result<Vec<f32>, ProcessingError> {
+    match sampling {
+        SegmentSampling::Zones(zones) => zone_fill_ratios(image, full_digit_location, zones),
+        SegmentSampling::Scanlines(scanlines) => {
+            scanline_crossing_ratios(image, full_digit_location, scanlines)
+        }
     }
+}
+
+/// Which segments of the digit are lit up, in the order top, top left, top right, centre, bottom
+/// left, bottom right and bottom
+pub fn lit_segments(
+    image: &Mat,
+    full_digit_location: Rect2i,
+    config: &SegmentConfig,
+    sampling: SegmentSampling,
+) -> Result<[bool; 7], ProcessingError> {
+    let segment_fill_ratios = segment_fill_ratios(image, full_digit_location, sampling)?;
+
+    Ok(std::array::from_fn(|segment| {
+        segment_fill_ratios[segment] > config.lit_threshold
+    }))
+}
+
+/// Reads the shape as one of the font's letters or its dash, if its lit up segments match one
+/// exactly and are not also one of the digits. Only used where a letter is expected, such as a
+/// device error code, as a digit with a faint segment can look just like a letter.
+pub fn parse_symbol(
+    image: &Mat,
+    full_digit_location: Rect2i,
+    config: &SegmentConfig,
+    sampling: SegmentSampling,
+) -> Result<Option<DigitParseResult>, ProcessingError> {
+    if narrow_one_shape(image, full_digit_location, config)?.is_some() {
+        return Ok(None);
+    }
+
+    let lit_segments = lit_segments(image, full_digit_location, config, sampling)?;
+
+    let is_digit_glyph = config
+        .font
+        .glyphs
+        .iter()
+        .any(|glyph| glyph.segments == lit_segments);
+
+    if is_digit_glyph {
+        return Ok(None);
+    }
+
+    Ok(config
+        .font
+        .symbols
+        .iter()
+        .find(|symbol| symbol.segments == lit_segments)
+        .map(|symbol| DigitParseResult::Symbol {
+            symbol: symbol.symbol,
+            segments: lit_segments,
+        }))
+}
+
+/// Reads the shape as the digit whose glyph its segments most likely show. Letters are never
+/// considered, so a digit with a faint segment is still read as that digit.
+pub fn parse_digit(
+    image: &Mat,
+    full_digit_location: Rect2i,
+    config: &SegmentConfig,
+    sampling: SegmentSampling,
+) -> Result<DigitParseResult, ProcessingError> {
+    if let Some((fill_ratio, width_to_height_ratio)) =
+        narrow_one_shape(image, full_digit_location, config)?
+    {
+        let confidence = distance_from_cutoff(fill_ratio, config.one_fill_ratio).min(
+            distance_from_cutoff(width_to_height_ratio, config.one_max_width_to_height_ratio),
+        );
+
+        return Ok(DigitParseResult::Digit(DigitConfidence {
+            digit: 1,
+            confidence,
+            runner_up: None,
+            margin: confidence,
+        }));
+    }
+
+    let segment_fill_ratios = segment_fill_ratios(image, full_digit_location, sampling)?;
+
+    let lit_up: Vec<i32> = segment_fill_ratios
+        .iter()
+        .map(|ratio| if *ratio > config.lit_threshold { 1 } else { 0 })
+        .collect();
+
+    // A digit is only as trustworthy as its least certain segment
+    let confidence = segment_fill_ratios
+        .iter()
+        .map(|ratio| distance_from_cutoff(*ratio, config.lit_threshold))
+        .fold(1.0, f32::min);
+
+    let lit_segments: [bool; 7] = std::array::from_fn(|segment| lit_up[segment] == 1);
+
+    let glyph_probabilities = glyph_probabilities(&segment_fill_ratios, config);
+
+    // A digit drawn in more than one way is as likely as all of its glyphs put together
+    let mut digit_probabilities: Vec<(i32, f32)> = Vec::new();
+
+    for (glyph, probability) in config.font.glyphs.iter().zip(&glyph_probabilities) {
+        match digit_probabilities
+            .iter_mut()
+            .find(|(digit, _)| *digit == glyph.digit)
+        {
+            Some((_, total)) => *total += probability,
+            None => digit_probabilities.push((glyph.digit, *probability)),
+        }
+    }
+
+    digit_probabilities
+        .sort_by(|(_, probability1), (_, probability2)| probability2.total_cmp(probability1));
+
+    let unrecognised = DigitParseResult::UnrecognisedSegments(lit_segments);
+
+    let Some(&(best_digit, best_probability)) = digit_probabilities.first() else {
+        return Ok(unrecognised);
+    };
+
+    let runner_up = digit_probabilities.get(1).copied();
+
+    // Compared against the most likely way of drawing the best digit
+    let closest_glyph = config
+        .font
+        .glyphs
+        .iter()
+        .zip(&glyph_probabilities)
+        .filter(|(glyph, _)| glyph.digit == best_digit)
+        .max_by(|(_, probability1), (_, probability2)| probability1.total_cmp(probability2))
+        .map(|(glyph, _)| glyph);
+
+    let mismatched_segments = closest_glyph
+        .map(|glyph| {
+            lit_up
+                .iter()
+                .zip(glyph.segments.iter())
+                .filter(|(lit, expected)| (**lit == 1) != **expected)
+                .count()
+        })
+        .unwrap_or(usize::MAX);
+
+    if mismatched_segments > config.max_mismatched_segments {
+        return Ok(unrecognised);
+    }
+
+    Ok(DigitParseResult::Digit(DigitConfidence {
+        digit: best_digit,
+        confidence,
+        runner_up: runner_up.map(|(digit, _)| digit),
+        margin: best_probability - runner_up.map(|(_, probability)| probability).unwrap_or(0.0),
+    }))
+}
 
-    let digit_width = ((full_digit_location.width as f32) * 0.25) as i32;
-    let digit_height = ((full_digit_location.height as f32) * 0.15) as i32;
-    let digit_height_centre = ((full_digit_location.height as f32) * 0.05) as i32;
+/// The fraction of a fixed zone of the digit's box for each segment that is filled in
+fn zone_fill_ratios(
+    image: &Mat,
+    full_digit_location: Rect2i,
+    zones: ZoneSampling,
+) -> Result<Vec<f32>, ProcessingError> {
+    let digit_width = ((full_digit_location.width as f32) * zones.side_width_ratio) as i32;
+    let digit_height = ((full_digit_location.height as f32) * zones.end_height_ratio) as i32;
+    let digit_height_centre =
+        ((full_digit_location.height as f32) * zones.centre_height_ratio / 2.) as i32;
 
     let segment_locations = [
         ((0, 0), (full_digit_location.width, digit_height)), // top row,
@@ -67,7 +264,7 @@ pub fn parse_digit(image: &Mat, full_digit_location: Rect2i) -> Result<i32, Proc
         ), // bottom row
     ];
 
-    let digit_segments_lit_up_result: [Result<i32, Error>; 7] =
+    let segment_fill_ratios_result: [Result<f32, Error>; 7] =
         segment_locations.map(|segment_locations| {
             let ((x_a, y_a), (x_b, y_b)) = segment_locations;
 
@@ -80,29 +277,200 @@ pub fn parse_digit(image: &Mat, full_digit_location: Rect2i) -> Result<i32, Proc
 
             let total_filled_in_area = count_non_zero(&focused_segment)?;
 
-            if (total_filled_in_area as f32 / rect.area() as f32) > 0.55 {
-                return Ok(1);
+            Ok(total_filled_in_area as f32 / rect.area() as f32)
+        });
+
+    let segment_fill_ratios: Vec<f32> = segment_fill_ratios_result
+        .into_iter()
+        .collect::<Result<Vec<f32>, Error>>()?;
+
+    Ok(segment_fill_ratios)
+}
+
+/// The fraction of the scanlines cast through each segment that cross a stroke there. Vertical
+/// scanlines down the middle of the box find the top, centre and bottom segments, and horizontal
+/// scanlines across the upper and lower halves find the left and right segments. Unlike fixed
+/// zones, a thin stroke counts just as much as a thick one.
+fn scanline_crossing_ratios(
+    image: &Mat,
+    full_digit_location: Rect2i,
+    scanlines: ScanlineSampling,
+) -> Result<Vec<f32>, ProcessingError> {
+    let offsets = scanlines.offsets();
+
+    let digit = image.roi(full_digit_location)?;
+    let width = full_digit_location.width;
+    let height = full_digit_location.height;
+
+    let is_lit =
+        |x: i32, y: i32| -> Result<bool, ProcessingError> { Ok(*digit.at_2d::<u8>(y, x)? > 0) };
+
+    let mut vertical_hits = [0; 3];
+
+    for offset in &offsets {
+        let x = (((0.5 + offset) * width as f32) as i32).clamp(0, width - 1);
+        let column = (0..height)
+            .map(|y| is_lit(x, y))
+            .collect::<Result<Vec<bool>, ProcessingError>>()?;
+
+        let mut crossed = [false; 3];
+
+        for (start, end) in strokes(&column) {
+            let centre = (start + end) as f32 / 2. / height as f32;
+
+            if centre < 1. / 3. {
+                crossed[0] = true;
+            } else if centre < 2. / 3. {
+                crossed[1] = true;
             } else {
-                return Ok(0);
+                crossed[2] = true;
             }
-        });
+        }
+
+        for (hits, crossed) in vertical_hits.iter_mut().zip(crossed) {
+            *hits += crossed as usize;
+        }
+    }
+
+    // Top left, top right, bottom left and bottom right
+    let mut horizontal_hits = [0; 4];
 
-    let digit_segments_lit_up: Result<Vec<i32>, Error> =
-        digit_segments_lit_up_result.into_iter().collect();
+    for (half, centre_y) in [0.25, 0.75].into_iter().enumerate() {
+        for offset in &offsets {
+            let y = (((centre_y + offset / 2.) * height as f32) as i32).clamp(0, height - 1);
+            let row = (0..width)
+                .map(|x| is_lit(x, y))
+                .collect::<Result<Vec<bool>, ProcessingError>>()?;
 
-    let lit_up = &digit_segments_lit_up?;
+            let mut crossed = [false; 2];
+
+            // A stroke across most of the box is a horizontal segment rather than a side of the digit
+            for (start, end) in strokes(&row)
+                .into_iter()
+                .filter(|(start, end)| ((end - start) as f32) < width as f32 / 2.)
+            {
+                let centre = (start + end) as f32 / 2. / width as f32;
+                crossed[(centre >= 0.5) as usize] = true;
+            }
+
+            for (side, crossed) in crossed.into_iter().enumerate() {
+                horizontal_hits[half * 2 + side] += crossed as usize;
+            }
+        }
+    }
 
-    let result = SEGMENTS_TO_NUMBER_MAP.iter().find(|segments| {
-        return lit_up
-            .into_iter()
-            .zip(segments.0.iter())
-            .all(|(a, b)| *a == *b);
-    });
+    let scanline_count = offsets.len() as f32;
+    let ratio = |hits: usize| hits as f32 / scanline_count;
+
+    Ok(vec![
+        ratio(vertical_hits[0]),
+        ratio(horizontal_hits[0]),
+        ratio(horizontal_hits[1]),
+        ratio(vertical_hits[1]),
+        ratio(horizontal_hits[2]),
+        ratio(horizontal_hits[3]),
+        ratio(vertical_hits[2]),
+    ])
+}
+
+/// The start and end, exclusive, of each run of lit up pixels along a scanline
+fn strokes(scanline: &[bool]) -> Vec<(usize, usize)> {
+    let mut strokes: Vec<(usize, usize)> = Vec::new();
+    let mut start: Option<usize> = None;
+
+    for (index, lit) in scanline.iter().enumerate() {
+        match (start, *lit) {
+            (None, true) => start = Some(index),
+            (Some(stroke_start), false) => {
+                strokes.push((stroke_start, index));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(stroke_start) = start {
+        strokes.push((stroke_start, scanline.len()));
+    }
+
+    strokes
+}
+
+/// How likely each of the font's glyphs is to be the one shown, in the order of the font. Each
+/// segment is lit up with a probability that rises smoothly with its fill ratio, so a faint segment
+/// counts against a glyph that expects it to be lit far less than an empty one does.
+fn glyph_probabilities(segment_fill_ratios: &[f32], config: &SegmentConfig) -> Vec<f32> {
+    let log_likelihoods: Vec<f32> = config
+        .font
+        .glyphs
+        .iter()
+        .map(|glyph| {
+            glyph
+                .segments
+                .iter()
+                .zip(segment_fill_ratios)
+                .map(|(expected, ratio)| {
+                    let lit_probability = (1.0
+                        / (1.0 + (-(ratio - config.lit_threshold) / config.lit_softness).exp()))
+                    .clamp(1e-6, 1.0 - 1e-6);
+
+                    if *expected {
+                        lit_probability.ln()
+                    } else {
+                        (1.0 - lit_probability).ln()
+                    }
+                })
+                .sum()
+        })
+        .collect();
+
+    // Normalised so that the probabilities of all of the glyphs add up to one
+    let most_likely = log_likelihoods.iter().copied().fold(f32::MIN, f32::max);
+    let unnormalised: Vec<f32> = log_likelihoods
+        .iter()
+        .map(|log_likelihood| (log_likelihood - most_likely).exp())
+        .collect();
+    let total: f32 = unnormalised.iter().sum();
+
+    unnormalised
+        .into_iter()
+        .map(|likelihood| likelihood / total)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_drawing::{draw_glyph, strokes};
+
+    #[test]
+    fn test_scanline_confidence_follows_offsets() {
+        let config = SegmentConfig::default();
+        let (thin_eight, location) = draw_glyph(strokes([true; 7], 3));
+
+        let confidence_of_eight = |scanlines: ScanlineSampling| match parse_digit(
+            &thin_eight,
+            location,
+            &config,
+            SegmentSampling::Scanlines(scanlines),
+        )
+        .unwrap()
+        {
+            DigitParseResult::Digit(digit) if digit.digit == 8 => digit.confidence,
+            _ => 0.,
+        };
+
+        assert_eq!(ScanlineSampling::default().offsets(), vec![-0.15, 0., 0.15]);
+
+        // Spread out so far that the outer scanlines run down the sides of the digit, and miss the
+        // top and bottom segments
+        let close_together = confidence_of_eight(ScanlineSampling::default());
+        let spread_out = confidence_of_eight(ScanlineSampling {
+            count: 3,
+            spread: 0.9,
+        });
 
-    match result {
-        Some(num) => Ok(num.1),
-        None => Err(ProcessingError::AppError(
-            crate::models::ReadingIdentificationError::CouldNotProcessSegments,
-        )),
+        assert!(close_together > 0.);
+        assert!(spread_out < close_together);
     }
 }
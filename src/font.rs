@@ -0,0 +1,203 @@
+use serde::{Deserialize, Serialize};
+
+/// One way of drawing a digit with the segments of a seven segment display
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Glyph {
+    pub digit: i32,
+    /// Which segments are lit up, in the order top, top left, top right, centre, bottom left,
+    /// bottom right and bottom
+    pub segments: [bool; 7],
+}
+
+/// A letter or dash a display may show in place of digits, e.g. in an error code such as `Err`
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SymbolGlyph {
+    pub symbol: char,
+    /// Which segments are lit up, in the same order as a digit's glyph
+    pub segments: [bool; 7],
+}
+
+impl SymbolGlyph {
+    pub fn new(symbol: char, segments: [u8; 7]) -> Self {
+        SymbolGlyph {
+            symbol,
+            segments: segments.map(|segment| segment == 1),
+        }
+    }
+}
+
+impl Glyph {
+    pub fn new(digit: i32, segments: [u8; 7]) -> Self {
+        Glyph {
+            digit,
+            segments: segments.map(|segment| segment == 1),
+        }
+    }
+}
+
+/// The glyphs a display draws its digits with. LCD vendors do not all draw 6, 7 and 9 the same
+/// way, so a digit may have more than one glyph.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SegmentFont {
+    pub name: String,
+    pub glyphs: Vec<Glyph>,
+    /// The letters and dashes the display can show. They are only looked for where a device code
+    /// may be shown instead of a reading, and a symbol drawn the same way as one of the digits is
+    /// always read as the digit.
+    #[serde(default = "SegmentFont::standard_symbols")]
+    pub symbols: Vec<SymbolGlyph>,
+}
+
+impl Default for SegmentFont {
+    fn default() -> Self {
+        SegmentFont::standard()
+    }
+}
+
+impl SegmentFont {
+    /// 6 and 9 with tails, and 7 with its top left segment lit up
+    pub fn standard() -> Self {
+        SegmentFont {
+            name: "standard".to_owned(),
+            glyphs: vec![
+                Glyph::new(0, [1, 1, 1, 0, 1, 1, 1]),
+                Glyph::new(1, [0, 0, 1, 0, 0, 1, 0]),
+                Glyph::new(2, [1, 0, 1, 1, 1, 0, 1]),
+                Glyph::new(3, [1, 0, 1, 1, 0, 1, 1]),
+                Glyph::new(4, [0, 1, 1, 1, 0, 1, 0]),
+                Glyph::new(5, [1, 1, 0, 1, 0, 1, 1]),
+                Glyph::new(6, [1, 1, 0, 1, 1, 1, 1]),
+                Glyph::new(7, [1, 1, 1, 0, 0, 1, 0]),
+                Glyph::new(8, [1, 1, 1, 1, 1, 1, 1]),
+                Glyph::new(9, [1, 1, 1, 1, 0, 1, 1]),
+            ],
+            symbols: SegmentFont::standard_symbols(),
+        }
+    }
+
+    /// The letters that can be drawn with seven segments and are used in device error and status
+    /// codes, such as `Err`, `E1`, `Lo` and `Hi`, along with the dash
+    pub fn standard_symbols() -> Vec<SymbolGlyph> {
+        vec![
+            SymbolGlyph::new('A', [1, 1, 1, 1, 1, 1, 0]),
+            SymbolGlyph::new('b', [0, 1, 0, 1, 1, 1, 1]),
+            SymbolGlyph::new('C', [1, 1, 0, 0, 1, 0, 1]),
+            SymbolGlyph::new('c', [0, 0, 0, 1, 1, 0, 1]),
+            SymbolGlyph::new('d', [0, 0, 1, 1, 1, 1, 1]),
+            SymbolGlyph::new('E', [1, 1, 0, 1, 1, 0, 1]),
+            SymbolGlyph::new('F', [1, 1, 0, 1, 1, 0, 0]),
+            SymbolGlyph::new('H', [0, 1, 1, 1, 1, 1, 0]),
+            SymbolGlyph::new('h', [0, 1, 0, 1, 1, 1, 0]),
+            SymbolGlyph::new('L', [0, 1, 0, 0, 1, 0, 1]),
+            SymbolGlyph::new('n', [0, 0, 0, 1, 1, 1, 0]),
+            SymbolGlyph::new('o', [0, 0, 0, 1, 1, 1, 1]),
+            SymbolGlyph::new('P', [1, 1, 1, 1, 1, 0, 0]),
+            SymbolGlyph::new('r', [0, 0, 0, 1, 1, 0, 0]),
+            SymbolGlyph::new('t', [0, 1, 0, 1, 1, 0, 1]),
+            SymbolGlyph::new('U', [0, 1, 1, 0, 1, 1, 1]),
+            SymbolGlyph::new('u', [0, 0, 0, 0, 1, 1, 1]),
+            SymbolGlyph::new('y', [0, 1, 1, 1, 0, 1, 1]),
+            SymbolGlyph::new('-', [0, 0, 0, 1, 0, 0, 0]),
+        ]
+    }
+
+    /// 6 and 9 without tails, and 7 drawn with only its top and right hand segments
+    pub fn tailless() -> Self {
+        SegmentFont::standard()
+            .with_name("tailless")
+            .without_digit(6)
+            .without_digit(7)
+            .without_digit(9)
+            .with_glyph(Glyph::new(6, [0, 1, 0, 1, 1, 1, 1]))
+            .with_glyph(Glyph::new(7, [1, 0, 1, 0, 0, 1, 0]))
+            .with_glyph(Glyph::new(9, [1, 1, 1, 1, 0, 1, 0]))
+    }
+
+    /// Both ways of drawing 6, 7 and 9, for reading displays from a mix of vendors
+    pub fn all_styles() -> Self {
+        SegmentFont::standard()
+            .with_name("all_styles")
+            .with_glyph(Glyph::new(6, [0, 1, 0, 1, 1, 1, 1]))
+            .with_glyph(Glyph::new(7, [1, 0, 1, 0, 0, 1, 0]))
+            .with_glyph(Glyph::new(9, [1, 1, 1, 1, 0, 1, 0]))
+    }
+
+    /// Every font that ships with the library
+    pub fn built_in_fonts() -> Vec<SegmentFont> {
+        vec![
+            SegmentFont::standard(),
+            SegmentFont::tailless(),
+            SegmentFont::all_styles(),
+        ]
+    }
+
+    /// Looks up a font that ships with the library by its name
+    pub fn built_in(name: &str) -> Option<SegmentFont> {
+        SegmentFont::built_in_fonts()
+            .into_iter()
+            .find(|font| font.name == name)
+    }
+
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = name.to_owned();
+        self
+    }
+
+    /// Adds another way of drawing a digit to the font
+    pub fn with_glyph(mut self, glyph: Glyph) -> Self {
+        self.glyphs.push(glyph);
+        self
+    }
+
+    /// Removes every glyph for the given digit, e.g. to replace them with a vendor's own
+    pub fn without_digit(mut self, digit: i32) -> Self {
+        self.glyphs.retain(|glyph| glyph.digit != digit);
+        self
+    }
+
+    /// Checks that every glyph is a digit and that no two digits are drawn the same way
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if self
+            .glyphs
+            .iter()
+            .any(|glyph| !(0..=9).contains(&glyph.digit))
+        {
+            return Err("Every glyph of the font must be a digit from 0 to 9");
+        }
+
+        for (index, glyph) in self.glyphs.iter().enumerate() {
+            let is_ambiguous = self.glyphs[index + 1..]
+                .iter()
+                .any(|other| other.segments == glyph.segments && other.digit != glyph.digit);
+
+            if is_ambiguous {
+                return Err("No two digits of the font may be drawn with the same segments");
+            }
+        }
+
+        let mut digits: Vec<i32> = self.glyphs.iter().map(|glyph| glyph.digit).collect();
+        digits.sort();
+        digits.dedup();
+
+        if digits.len() < 2 {
+            return Err("The font must have glyphs for at least two digits");
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fonts_validated() {
+        for font in SegmentFont::built_in_fonts() {
+            assert_eq!(font.validate(), Ok(()));
+        }
+
+        let ambiguous = SegmentFont::standard().with_glyph(Glyph::new(1, [1, 1, 1, 1, 1, 1, 1]));
+        assert!(ambiguous.validate().is_err());
+    }
+}
@@ -1,150 +1,193 @@
 use std::sync::Arc;
 
 use crate::{
+    classifier::{ClassifierMode, DigitClassifier},
+    config::{DigitLocationConfig, SegmentConfig, SegmentSampling},
+    date_time_extractor,
     debug::BpmOcrDebugOutputter,
-    digit_extractor,
-    models::{BloodPressureReading, ProcessingError, ReadingIdentificationError, ReadingLocations},
-};
-use opencv::{
-    Error,
-    core::{Mat, Point, Rect2i, Size, Vector},
-    imgproc::{
-        self, THRESH_BINARY_INV, THRESH_OTSU, bounding_rect, dilate_def, find_contours_def,
-        get_structuring_element_def, threshold,
+    models::{
+        DebuggerTrace, DigitConfidence, FieldConfidence, Indicator, IndicatorFlags, PartialReading,
+        PressureUnit, ProcessingError, ReadingField, ReadingIdentificationError, ReadingLocations,
+        ReadingWithConfidence, ScreenReading,
     },
+    profile::{DigitCount, FieldRole, LayoutFingerprint, MonitorProfile, ProfileSelection},
+    seven_segment::{DigitParseResult, SevenSegmentReader},
 };
+use opencv::core::{Mat, MatTraitConst, Point, Rect2i, Size, count_non_zero};
+
+/// Dashes narrower than this fraction of the height of the LCD screen are not part of a device code
+const MIN_DASH_WIDTH_RATIO: f32 = 0.05;
+
+/// A device code must span at least this fraction of the width of the LCD screen, as the rows of a
+/// reading it stands in for do
+const MIN_DEVICE_CODE_WIDTH_RATIO: f32 = 0.25;
+
+/// How much a profile's match score is scaled down by when its date and time region has no digits
+/// in it, so that detection prefers the same layout without a date and time
+const EMPTY_DATE_TIME_SCORE_FACTOR: f32 = 0.5;
 
-pub(crate) struct LcdNumberExtractor<T: BpmOcrDebugOutputter> {
-    debugger: Arc<T>,
-    debug_session_name: String,
+/// Reads a blood pressure reading from the numbers on a monitor's LCD screen, using a monitor
+/// profile to decide which number is which
+pub(crate) struct LcdNumberExtractor {
+    reader: SevenSegmentReader,
 }
 
-impl<T: BpmOcrDebugOutputter> LcdNumberExtractor<T> {
-    pub fn new(debugger: Arc<T>, unique_trace_name: &str) -> Self {
+impl LcdNumberExtractor {
+    pub fn new(digit_config: DigitLocationConfig, segment_config: SegmentConfig) -> Self {
         LcdNumberExtractor {
-            debugger: debugger,
-            debug_session_name: unique_trace_name.to_owned(),
+            reader: SevenSegmentReader::new(digit_config, segment_config),
         }
     }
 
-    fn highlight_digits(self: &Self, image: &Mat) -> Result<Mat, ProcessingError> {
-        let mut thresholed_image = Mat::default();
-
-        threshold(
-            image,
-            &mut thresholed_image,
-            0.,
-            255.,
-            THRESH_BINARY_INV | THRESH_OTSU,
-        )?;
-
-        self.debugger
-            .debug_digits_before_morph(&self.debug_session_name, &thresholed_image)?;
-
-        let mut dilated_image = Mat::default();
-
-        // Fill in the gaps in the middle of the digits on the LCD screen to make it easier to identify the full digit
-        let dilation_kernel = get_structuring_element_def(imgproc::MORPH_RECT, Size::new(3, 3))?;
-        dilate_def(&thresholed_image, &mut dilated_image, &dilation_kernel)?;
-
-        self.debugger
-            .debug_digits_after_dilation(&self.debug_session_name, &dilated_image)?;
-
-        return Ok(dilated_image);
+    pub fn with_classifier(self, classifier: Arc<DigitClassifier>, mode: ClassifierMode) -> Self {
+        LcdNumberExtractor {
+            reader: self.reader.with_classifier(classifier, mode),
+        }
     }
 
-    pub fn get_digit_borders(self: &Self, image: &Mat) -> Result<Vec<Rect2i>, ProcessingError> {
-        let mut contours_output: Vector<Vector<Point>> = Vector::new();
-        find_contours_def(
-            image,
-            &mut contours_output,
-            imgproc::RETR_EXTERNAL,
-            imgproc::CHAIN_APPROX_SIMPLE,
-        )?;
-
-        let predicted_digits: Vec<Rect2i> = contours_output
-            .into_iter()
-            .map(|contour| {
-                return bounding_rect(&contour);
-            })
-            .filter(|possible_digit| match possible_digit {
-                Ok(rect) => rect.y != 0 && rect.x != 0 && rect.height > 30,
-                _ => true, // Make sure errors are propagated
-            })
-            .collect::<Result<Vec<Rect2i>, Error>>()?;
-
-        return Ok(predicted_digits);
+    /// Decides which unit the blood pressure is shown in. A lit unit indicator is preferred, then
+    /// decimal points in both pressures, which only kPa is shown with, and then the profile's unit.
+    fn pressure_unit(
+        self: &Self,
+        profile: &MonitorProfile,
+        indicators: &IndicatorFlags,
+        shows_decimal_points: bool,
+    ) -> PressureUnit {
+        if indicators.is_lit(Indicator::KpaUnit) {
+            PressureUnit::KPa
+        } else if indicators.is_lit(Indicator::MmHgUnit) {
+            PressureUnit::MmHg
+        } else if shows_decimal_points {
+            PressureUnit::KPa
+        } else {
+            profile.pressure_unit
+        }
     }
 
-    fn group_by_similar_y_coordinate(
+    /// Picks the monitor profile to read the LCD screen with from the layout of its digits. Each
+    /// profile is matched against the digits outside of its date and time region.
+    fn select_profile<'a>(
         self: &Self,
-        digits: Vec<Rect2i>,
-        difference_threshold: i32,
-    ) -> Vec<Vec<Rect2i>> {
-        let mut groups: Vec<Vec<Rect2i>> = Vec::new();
-
-        'outer: for digit in digits {
-            for group in groups.iter_mut() {
-                if let Some(leader) = group.first() {
-                    if (leader.y - digit.y).abs() < difference_threshold {
-                        group.push(digit);
-                        continue 'outer;
-                    }
-                }
+        digits: &[Rect2i],
+        lcd_size: Size,
+        profiles: &'a ProfileSelection,
+    ) -> Result<&'a MonitorProfile, ProcessingError> {
+        let selected = profiles.select_with(|profile| {
+            let reading_digits = digits_outside_date_time(digits, profile, lcd_size);
+            let found_date_time = reading_digits.len() < digits.len();
+
+            let rows = self.reader.group_into_rows(reading_digits, lcd_size.height);
+            let score = profile.match_score(&LayoutFingerprint::from_rows(&rows))?;
+
+            // Otherwise a profile with a date and time would tie with the same profile without one
+            if profile.date_time.is_some() && !found_date_time {
+                Some(score * EMPTY_DATE_TIME_SCORE_FACTOR)
+            } else {
+                Some(score)
             }
+        });
 
-            // No matching group found — make a new one
-            groups.push(vec![digit]);
-        }
+        selected.ok_or_else(|| {
+            let rows = self
+                .reader
+                .group_into_rows(digits.to_vec(), lcd_size.height);
 
-        groups
+            ProcessingError::AppError(ReadingIdentificationError::NoMatchingMonitorProfile(
+                LayoutFingerprint::from_rows(&rows),
+            ))
+        })
     }
 
     pub fn get_reading_locations(
         self: &Self,
-        mut digits: Vec<Rect2i>,
+        digits: Vec<Rect2i>,
+        lcd_height: i32,
+        profile: &MonitorProfile,
     ) -> Result<ReadingLocations, ProcessingError> {
-        // Sort digits by their row
+        let grouped_by_y_coordinate = self.reader.group_into_rows(digits, lcd_height);
+
+        if grouped_by_y_coordinate.len() != profile.rows.len() {
+            return Err(ProcessingError::AppError(
+                ReadingIdentificationError::UnexpectedNumberOfRows {
+                    expected: profile.rows.len(),
+                    found: grouped_by_y_coordinate.len(),
+                },
+            ));
+        }
 
-        digits.sort_by(|vec1, vec2| vec1.y.cmp(&vec2.y));
+        let mut systolic_region = None;
+        let mut diastolic_region = None;
+        let mut pulse_region = None;
 
-        let mut grouped_by_y_coordinate: Vec<Vec<Rect2i>> =
-            self.group_by_similar_y_coordinate(digits, 5);
+        for (row_index, (row, row_layout)) in grouped_by_y_coordinate
+            .into_iter()
+            .zip(&profile.rows)
+            .enumerate()
+        {
+            let fields = split_row_into_fields(row, row_layout.fields.len());
+
+            for (field_digits, field_layout) in fields.into_iter().zip(&row_layout.fields) {
+                if !field_layout.digits.contains(field_digits.len()) {
+                    return Err(ProcessingError::AppError(
+                        ReadingIdentificationError::UnexpectedNumberOfDigits {
+                            row: row_index,
+                            role: field_layout.role,
+                            expected: field_layout.digits,
+                            found: field_digits.len(),
+                        },
+                    ));
+                }
 
-        // Sort numbers by their columns
-        for group in grouped_by_y_coordinate.iter_mut() {
-            group.sort_by(|item1, item2| item1.x.cmp(&item2.x));
+                match field_layout.role {
+                    FieldRole::Systolic => systolic_region = Some(field_digits),
+                    FieldRole::Diastolic => diastolic_region = Some(field_digits),
+                    FieldRole::Pulse => pulse_region = Some(field_digits),
+                    FieldRole::Ignored => {}
+                }
+            }
         }
 
-        match (
-            grouped_by_y_coordinate.pop(),
-            grouped_by_y_coordinate.pop(),
-            grouped_by_y_coordinate.pop(),
-            grouped_by_y_coordinate.pop(),
-        ) {
-            (Some(pulse), Some(diastolic), Some(systolic), None) => {
-                return Ok(ReadingLocations {
-                    systolic_region: systolic,
-                    diastolic_region: diastolic,
-                    pulse_region: pulse,
-                });
-            }
-            _ => {
-                return Err(ProcessingError::AppError(
-                    crate::models::ReadingIdentificationError::UnexpectedNumberOfRows,
-                ));
-            }
+        match (systolic_region, diastolic_region, pulse_region) {
+            (Some(systolic), Some(diastolic), Some(pulse)) => Ok(ReadingLocations {
+                profile_name: profile.name.clone(),
+                systolic_region: systolic,
+                diastolic_region: diastolic,
+                pulse_region: pulse,
+            }),
+            _ => Err(ProcessingError::AppError(
+                ReadingIdentificationError::InvalidMonitorProfile(
+                    "The profile is missing a systolic, diastolic or pulse field",
+                ),
+            )),
         }
     }
 
     fn digits_to_number(
         self: &Self,
         image: &Mat,
-        digits: Vec<Rect2i>,
-    ) -> Result<i32, ProcessingError> {
+        digits: &[Rect2i],
+        field: ReadingField,
+        sampling: SegmentSampling,
+    ) -> Result<FieldConfidence, ProcessingError> {
         let mut result: i32 = 0;
+        let mut digit_confidences: Vec<DigitConfidence> = Vec::new();
+
         for (index, digit) in digits.iter().enumerate() {
-            let digit_result: i32 = digit_extractor::parse_digit(&image, *digit)?;
+            let digit_confidence = match self.reader.read_digit_sampled(&image, *digit, sampling)? {
+                DigitParseResult::Digit(digit_confidence) => digit_confidence,
+                DigitParseResult::UnrecognisedSegments(segments)
+                | DigitParseResult::Symbol { segments, .. } => {
+                    return Err(ProcessingError::AppError(
+                        ReadingIdentificationError::CouldNotProcessSegments {
+                            field,
+                            digit_index: index,
+                            segments,
+                            partial_reading: PartialReading::default(),
+                        },
+                    ));
+                }
+            };
+            let digit_result: i32 = digit_confidence.digit;
             let multiplier: u32 = (digits.len() - (index + 1)).try_into().map_err(|_| {
                 ProcessingError::AppError(ReadingIdentificationError::InternalError(
                     "Unexpected number conversion issue",
@@ -153,40 +196,575 @@ impl<T: BpmOcrDebugOutputter> LcdNumberExtractor<T> {
 
             let ten: i32 = 10;
             result = result + (digit_result * (ten.pow(multiplier)));
+            digit_confidences.push(digit_confidence);
+        }
+
+        let confidence = digit_confidences
+            .iter()
+            .map(|digit| digit.confidence)
+            .fold(1.0, f32::min);
+
+        Ok(FieldConfidence {
+            value: result,
+            decimal_places: 0,
+            confidence,
+            digits: digit_confidences,
+        })
+    }
+
+    /// Decides which of the indicator icons the profile describes are lit, by how much of each
+    /// icon's region is filled in
+    fn detect_indicators<T: BpmOcrDebugOutputter>(
+        self: &Self,
+        image: &Mat,
+        profile: &MonitorProfile,
+        debugging_session: &DebuggerTrace<T>,
+    ) -> Result<IndicatorFlags, ProcessingError> {
+        let mut indicators = IndicatorFlags::default();
+        let mut indicator_regions: Vec<(Rect2i, bool)> = Vec::new();
+
+        for indicator_layout in &profile.indicators {
+            let region = indicator_layout.region.to_rect(image.cols(), image.rows());
+
+            let lit = if region.area() > 0 {
+                let filled_in_area = count_non_zero(&image.roi(region)?)?;
+                filled_in_area as f32 / region.area() as f32 >= indicator_layout.min_fill_ratio
+            } else {
+                false
+            };
+
+            indicators.set_lit(indicator_layout.indicator, lit);
+            indicator_regions.push((region, lit));
         }
 
-        Ok(result)
+        debugging_session.debugger.debug_indicator_regions(
+            &debugging_session.unique_trace_name,
+            image,
+            &indicator_regions,
+        )?;
+
+        Ok(indicators)
     }
 
-    pub fn extract_reading(
+    /// Looks for a row of the screen showing a device error or status code, e.g. `Err`, `E1`, `Lo` or
+    /// `---`. Lower case letters only fill the bottom half of a digit, so rows are made up of shapes
+    /// that overlap vertically and each shape is read as if it were as tall as its row. The code must
+    /// fill a row of the reading, having as many characters as one of `row_lengths` allows, so that
+    /// a few letter shaped marks on something other than the screen are not taken for a code.
+    fn find_device_code(
         self: &Self,
         image: &Mat,
-    ) -> Result<BloodPressureReading, ProcessingError> {
-        let highlighted_digits = self.highlight_digits(image)?;
+        digits: &[Rect2i],
+        small_shapes: &[Rect2i],
+        sampling: SegmentSampling,
+        row_lengths: &[DigitCount],
+    ) -> Result<Option<String>, ProcessingError> {
+        let min_dash_width = (MIN_DASH_WIDTH_RATIO * image.rows() as f32) as i32;
+        let min_code_width = (MIN_DEVICE_CODE_WIDTH_RATIO * image.cols() as f32) as i32;
+
+        let dashes = small_shapes
+            .iter()
+            .filter(|shape| shape.width >= 2 * shape.height && shape.width >= min_dash_width)
+            .map(|shape| (*shape, true));
+
+        let mut shapes: Vec<(Rect2i, bool)> = digits
+            .iter()
+            .map(|digit| (*digit, false))
+            .chain(dashes)
+            .collect();
+        shapes.sort_by(|(shape1, _), (shape2, _)| shape1.x.cmp(&shape2.x));
+
+        let mut rows: Vec<Vec<(Rect2i, bool)>> = Vec::new();
+
+        for (shape, is_dash) in shapes {
+            let centre_y = shape.y + shape.height / 2;
+
+            let row = rows.iter_mut().find(|row| {
+                let top = row.iter().map(|(other, _)| other.y).min().unwrap_or(0);
+                let bottom = row
+                    .iter()
+                    .map(|(other, _)| other.y + other.height)
+                    .max()
+                    .unwrap_or(0);
+
+                top <= centre_y && centre_y <= bottom
+            });
+
+            match row {
+                Some(row) => row.push((shape, is_dash)),
+                None => rows.push(vec![(shape, is_dash)]),
+            }
+        }
 
-        let digit_borders = self.get_digit_borders(&highlighted_digits)?;
+        rows.sort_by_key(|row| row.iter().map(|(shape, _)| shape.y).min().unwrap_or(0));
 
-        self.debugger.debug_digit_locations(
-            &self.debug_session_name,
+        'rows: for row in rows {
+            let glyphs: Vec<&Rect2i> = row
+                .iter()
+                .filter(|(_, is_dash)| !is_dash)
+                .map(|(shape, _)| shape)
+                .collect();
+
+            let top = glyphs.iter().map(|shape| shape.y).min().unwrap_or(0);
+            let bottom = glyphs
+                .iter()
+                .map(|shape| shape.y + shape.height)
+                .max()
+                .unwrap_or(0);
+
+            let mut code = String::new();
+
+            for (shape, is_dash) in &row {
+                if *is_dash {
+                    code.push('-');
+                    continue;
+                }
+
+                let full_height = Rect2i::new(shape.x, top, shape.width, bottom - top);
+
+                match self
+                    .reader
+                    .read_character_sampled(image, full_height, sampling)?
+                {
+                    DigitParseResult::Digit(digit_confidence) => {
+                        code.push_str(&digit_confidence.digit.to_string())
+                    }
+                    DigitParseResult::Symbol { symbol, .. } => code.push(symbol),
+                    DigitParseResult::UnrecognisedSegments(_) => continue 'rows,
+                }
+            }
+
+            let has_letter = code.chars().any(|symbol| symbol.is_alphabetic());
+            let dash_count = code.chars().filter(|symbol| *symbol == '-').count();
+
+            let left = row.iter().map(|(shape, _)| shape.x).min().unwrap_or(0);
+            let right = row
+                .iter()
+                .map(|(shape, _)| shape.x + shape.width)
+                .max()
+                .unwrap_or(0);
+
+            let fills_row = right - left >= min_code_width
+                && row_lengths
+                    .iter()
+                    .any(|length| length.contains(code.chars().count()));
+
+            if fills_row && (has_letter || dash_count >= 2) {
+                return Ok(Some(code));
+            }
+        }
+
+        Ok(None)
+    }
+
+    pub fn extract_reading<T: BpmOcrDebugOutputter>(
+        self: &Self,
+        image: &Mat,
+        profiles: &ProfileSelection,
+        debugging_session: &DebuggerTrace<T>,
+    ) -> Result<ScreenReading, ProcessingError> {
+        let highlighted_digits = self.reader.highlight(image, debugging_session)?;
+
+        let shapes = self.reader.find_shapes(&highlighted_digits)?;
+        let (digit_borders, small_shapes) = (shapes.digits, shapes.small_shapes);
+
+        debugging_session.debugger.debug_digit_locations(
+            &debugging_session.unique_trace_name,
             &highlighted_digits,
             &digit_borders,
         )?;
 
-        let reading_locations = self.get_reading_locations(digit_borders)?;
+        let result = self.read_fields(
+            &highlighted_digits,
+            digit_borders.clone(),
+            &small_shapes,
+            profiles,
+            debugging_session,
+        );
+
+        // A screen that could not be read as a reading may be showing an error code instead
+        match result {
+            Err(ProcessingError::AppError(error)) if error.may_be_device_code() => {
+                let lcd_size = Size::new(highlighted_digits.cols(), highlighted_digits.rows());
+
+                // The code is drawn the same way, and in place of, a row of the reading. Any of the
+                // profiles might be showing it if the screen did not match one.
+                let (sampling, row_profiles) =
+                    match self.select_profile(&digit_borders, lcd_size, profiles) {
+                        Ok(profile) => (
+                            self.segment_sampling(profile),
+                            std::slice::from_ref(profile),
+                        ),
+                        Err(_) => (self.reader.segment_config().sampling, profiles.profiles()),
+                    };
+
+                let row_lengths: Vec<DigitCount> = row_profiles
+                    .iter()
+                    .flat_map(|profile| profile.rows.iter().map(|row| row.digit_count()))
+                    .collect();
+
+                match self.find_device_code(
+                    &highlighted_digits,
+                    &digit_borders,
+                    &small_shapes,
+                    sampling,
+                    &row_lengths,
+                )? {
+                    Some(code) => Err(ProcessingError::AppError(
+                        ReadingIdentificationError::DeviceReportedError { code },
+                    )),
+                    None => Err(ProcessingError::AppError(error)),
+                }
+            }
+            result => result,
+        }
+    }
+
+    /// Picks the profile for the screen and finds which of its digits make up each field of the reading
+    fn locate_fields<'a>(
+        self: &Self,
+        highlighted_digits: &Mat,
+        digit_borders: Vec<Rect2i>,
+        profiles: &'a ProfileSelection,
+    ) -> Result<(&'a MonitorProfile, ReadingLocations), ProcessingError> {
+        let lcd_size = Size::new(highlighted_digits.cols(), highlighted_digits.rows());
+        let profile = self.select_profile(&digit_borders, lcd_size, profiles)?;
+
+        let digit_borders = digits_outside_date_time(&digit_borders, profile, lcd_size);
+
+        let reading_locations =
+            self.get_reading_locations(digit_borders, highlighted_digits.rows(), profile)?;
+
+        Ok((profile, reading_locations))
+    }
+
+    /// Finds where each field of the reading is on the screen without reading any of its digits,
+    /// along with the highlighted screen the locations are within
+    pub fn locate_reading<T: BpmOcrDebugOutputter>(
+        self: &Self,
+        image: &Mat,
+        profiles: &ProfileSelection,
+        debugging_session: &DebuggerTrace<T>,
+    ) -> Result<(Mat, ReadingLocations), ProcessingError> {
+        let highlighted_digits = self.reader.highlight(image, debugging_session)?;
+        let shapes = self.reader.find_shapes(&highlighted_digits)?;
+
+        let (_, reading_locations) =
+            self.locate_fields(&highlighted_digits, shapes.digits, profiles)?;
+
+        Ok((highlighted_digits, reading_locations))
+    }
+
+    /// How the segments of the profile's digits are sampled, unless it leaves that to the configuration
+    fn segment_sampling(self: &Self, profile: &MonitorProfile) -> SegmentSampling {
+        profile
+            .segment_sampling
+            .unwrap_or(self.reader.segment_config().sampling)
+    }
+
+    /// Reads the fields of the reading from the digits on the screen, using the profile that fits
+    /// the layout of the digits best
+    fn read_fields<T: BpmOcrDebugOutputter>(
+        self: &Self,
+        highlighted_digits: &Mat,
+        digit_borders: Vec<Rect2i>,
+        small_shapes: &[Rect2i],
+        profiles: &ProfileSelection,
+        debugging_session: &DebuggerTrace<T>,
+    ) -> Result<ScreenReading, ProcessingError> {
+        let (profile, reading_locations) =
+            self.locate_fields(highlighted_digits, digit_borders, profiles)?;
+
+        let sampling = self.segment_sampling(profile);
+
+        // Every field is attempted, even once one has failed, so that the error can say which fields could be read
+        let systolic_result = self.digits_to_number(
+            highlighted_digits,
+            &reading_locations.systolic_region,
+            ReadingField::Systolic,
+            sampling,
+        );
+        let diastolic_result = self.digits_to_number(
+            highlighted_digits,
+            &reading_locations.diastolic_region,
+            ReadingField::Diastolic,
+            sampling,
+        );
+        let pulse_result = self.digits_to_number(
+            highlighted_digits,
+            &reading_locations.pulse_region,
+            ReadingField::Pulse,
+            sampling,
+        );
+
+        match (systolic_result, diastolic_result, pulse_result) {
+            (Ok(mut systolic), Ok(mut diastolic), Ok(pulse)) => {
+                let indicators =
+                    self.detect_indicators(highlighted_digits, profile, debugging_session)?;
+
+                let systolic_decimal_places = self
+                    .reader
+                    .find_decimal_places(&reading_locations.systolic_region, small_shapes);
+                let diastolic_decimal_places = self
+                    .reader
+                    .find_decimal_places(&reading_locations.diastolic_region, small_shapes);
+
+                let unit = self.pressure_unit(
+                    profile,
+                    &indicators,
+                    systolic_decimal_places.is_some() && diastolic_decimal_places.is_some(),
+                );
+
+                // kPa is always shown to one decimal place, so that is assumed if the decimal point could not be seen
+                if let PressureUnit::KPa = unit {
+                    systolic.decimal_places = systolic_decimal_places.unwrap_or(1);
+                    diastolic.decimal_places = diastolic_decimal_places.unwrap_or(1);
+                }
+
+                let blood_pressure_reading = ReadingWithConfidence {
+                    systolic,
+                    diastolic,
+                    pulse,
+                    unit,
+                };
+
+                let date_time = match &profile.date_time {
+                    Some(date_time_layout) => date_time_extractor::read_date_time(
+                        highlighted_digits,
+                        date_time_layout,
+                        &self.reader,
+                        sampling,
+                    )?,
+                    None => None,
+                };
+
+                return Ok(ScreenReading {
+                    reading: blood_pressure_reading,
+                    locations: reading_locations,
+                    indicators,
+                    date_time,
+                });
+            }
+            (systolic, diastolic, pulse) => {
+                let read_fields = PartialReading {
+                    systolic: systolic.as_ref().ok().map(|field| field.value),
+                    diastolic: diastolic.as_ref().ok().map(|field| field.value),
+                    pulse: pulse.as_ref().ok().map(|field| field.value),
+                };
+
+                let first_error = [systolic.err(), diastolic.err(), pulse.err()]
+                    .into_iter()
+                    .flatten()
+                    .next()
+                    .unwrap_or(ProcessingError::AppError(
+                        ReadingIdentificationError::InternalError(
+                            "Internal error: a field failed without an error",
+                        ),
+                    ));
+
+                return Err(with_partial_reading(first_error, read_fields));
+            }
+        }
+    }
+}
+
+/// The digits that are not within the profile's date and time region, as the tall date and time
+/// digits would otherwise show up as an extra row
+fn digits_outside_date_time(
+    digits: &[Rect2i],
+    profile: &MonitorProfile,
+    lcd_size: Size,
+) -> Vec<Rect2i> {
+    match &profile.date_time {
+        Some(date_time_layout) => {
+            let date_time_region = date_time_layout
+                .region
+                .to_rect(lcd_size.width, lcd_size.height);
+
+            digits
+                .iter()
+                .filter(|digit| {
+                    !date_time_region.contains(Point::new(
+                        digit.x + digit.width / 2,
+                        digit.y + digit.height / 2,
+                    ))
+                })
+                .cloned()
+                .collect()
+        }
+        None => digits.to_vec(),
+    }
+}
+
+/// Splits a row of digits, sorted from left to right, into the given number of fields at the
+/// widest gaps between neighbouring digits. A row with too few digits has empty fields at its end.
+fn split_row_into_fields(row: Vec<Rect2i>, field_count: usize) -> Vec<Vec<Rect2i>> {
+    let mut gaps: Vec<(usize, i32)> = row
+        .windows(2)
+        .enumerate()
+        .map(|(index, pair)| (index + 1, pair[1].x - (pair[0].x + pair[0].width)))
+        .collect();
+
+    gaps.sort_by(|gap1, gap2| gap2.1.cmp(&gap1.1));
+
+    let mut split_points: Vec<usize> = gaps
+        .into_iter()
+        .take(field_count.saturating_sub(1))
+        .map(|(index, _)| index)
+        .collect();
+
+    split_points.sort();
+
+    let mut fields: Vec<Vec<Rect2i>> = Vec::with_capacity(field_count);
+    let mut remaining = row;
+
+    for split_point in split_points.into_iter().rev() {
+        fields.push(remaining.split_off(split_point));
+    }
 
-        let systolic_result =
-            self.digits_to_number(&highlighted_digits, reading_locations.systolic_region)?;
-        let diastolic_result =
-            self.digits_to_number(&highlighted_digits, reading_locations.diastolic_region)?;
-        let pulse_result =
-            self.digits_to_number(&highlighted_digits, reading_locations.pulse_region)?;
+    fields.push(remaining);
+    fields.reverse();
+    fields.resize(field_count.max(1), Vec::new());
 
-        let blood_pressure_reading = BloodPressureReading {
-            systolic: systolic_result,
-            diastolic: diastolic_result,
-            pulse: pulse_result,
+    fields
+}
+
+fn with_partial_reading(error: ProcessingError, read_fields: PartialReading) -> ProcessingError {
+    match error {
+        ProcessingError::AppError(ReadingIdentificationError::CouldNotProcessSegments {
+            field,
+            digit_index,
+            segments,
+            partial_reading: _,
+        }) => ProcessingError::AppError(ReadingIdentificationError::CouldNotProcessSegments {
+            field,
+            digit_index,
+            segments,
+            partial_reading: read_fields,
+        }),
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::font::SegmentFont;
+    use crate::test_drawing::{draw_glyphs, strokes};
+
+    #[test]
+    fn test_profile_detected_without_date_time_digits() {
+        let extractor =
+            LcdNumberExtractor::new(DigitLocationConfig::default(), SegmentConfig::default());
+        let lcd_size = Size::new(400, 400);
+
+        let row = |y: i32, height: i32, digit_count: i32| -> Vec<Rect2i> {
+            (0..digit_count)
+                .map(|index| Rect2i::new(50 + index * 70, y, height / 2, height))
+                .collect()
         };
 
-        return Ok(blood_pressure_reading);
+        let reading_digits: Vec<Rect2i> =
+            [row(100, 60, 3), row(200, 60, 2), row(300, 60, 2)].concat();
+        let with_date_time: Vec<Rect2i> = [row(5, 20, 4), reading_digits.clone()].concat();
+
+        let profiles = ProfileSelection::Detect(vec![
+            MonitorProfile::date_time_above_stacked(),
+            MonitorProfile::stacked(),
+        ]);
+
+        // The date and time digits are left out of the rows before the layout is matched
+        let profile = extractor
+            .select_profile(&with_date_time, lcd_size, &profiles)
+            .unwrap();
+        assert_eq!(profile.name, "date_time_above_stacked");
+
+        // With nothing in its date and time region, the profile no longer ties with `stacked`
+        let profile = extractor
+            .select_profile(&reading_digits, lcd_size, &profiles)
+            .unwrap();
+        assert_eq!(profile.name, "stacked");
+    }
+
+    #[test]
+    fn test_pressure_unit_inferred_from_decimal_points() {
+        let extractor =
+            LcdNumberExtractor::new(DigitLocationConfig::default(), SegmentConfig::default());
+        let no_indicators = IndicatorFlags::default();
+
+        let stacked = MonitorProfile::stacked();
+        let stacked_kpa = MonitorProfile::stacked_kpa();
+
+        assert_eq!(
+            extractor.pressure_unit(&stacked, &no_indicators, true),
+            PressureUnit::KPa
+        );
+        assert_eq!(
+            extractor.pressure_unit(&stacked, &no_indicators, false),
+            PressureUnit::MmHg
+        );
+        assert_eq!(
+            extractor.pressure_unit(&stacked_kpa, &no_indicators, false),
+            PressureUnit::KPa
+        );
+
+        // A lit unit indicator is trusted over everything else
+        let mut mmhg_lit = IndicatorFlags::default();
+        mmhg_lit.set_lit(Indicator::MmHgUnit, true);
+
+        assert_eq!(
+            extractor.pressure_unit(&stacked_kpa, &mmhg_lit, true),
+            PressureUnit::MmHg
+        );
+    }
+
+    #[test]
+    fn test_device_code_found_instead_of_reading() {
+        let reader = SevenSegmentReader::default();
+        let extractor =
+            LcdNumberExtractor::new(DigitLocationConfig::default(), SegmentConfig::default());
+
+        let letter = |symbol: char| {
+            let glyph = SegmentFont::standard_symbols()
+                .into_iter()
+                .find(|glyph| glyph.symbol == symbol)
+                .unwrap();
+
+            strokes(glyph.segments, 10)
+        };
+        let digit = |digit: i32| {
+            let glyph = SegmentFont::standard()
+                .glyphs
+                .into_iter()
+                .find(|glyph| glyph.digit == digit)
+                .unwrap();
+
+            strokes(glyph.segments, 10)
+        };
+
+        let displays = [
+            (vec![letter('E'), letter('r'), letter('r')], Some("Err")),
+            (vec![letter('-'), letter('-'), letter('-')], Some("---")),
+            (vec![digit(1), digit(2), digit(0)], None),
+            // Too short to fill a row of the reading
+            (vec![letter('E')], None),
+        ];
+
+        for (glyphs, expected_code) in displays {
+            let (image, _) = draw_glyphs(&glyphs);
+            let shapes = reader.find_shapes(&image).unwrap();
+
+            let code = extractor
+                .find_device_code(
+                    &image,
+                    &shapes.digits,
+                    &shapes.small_shapes,
+                    SegmentSampling::zones(),
+                    &[DigitCount::between(2, 3)],
+                )
+                .unwrap();
+
+            assert_eq!(code.as_deref(), expected_code);
+        }
     }
 }
@@ -1,52 +1,66 @@
-use std::{cmp::max, sync::Arc};
-
 use opencv::{
     Error,
-    core::{Mat, Point, Point2f, Size, UMat, Vector, VectorToVec},
+    core::{
+        CV_8U, Mat, MatTraitConst, Point, Point2f, Scalar, Size, UMat, Vector, VectorToVec,
+        mean_std_dev,
+    },
     imgproc::{
         self, approx_poly_dp, arc_length, get_perspective_transform_def, warp_perspective_def,
     },
 };
 
 use crate::{
+    config::LcdScreenConfig,
     debug::BpmOcrDebugOutputter,
     models::{
-        self, LcdScreenCandidate, LcdScreenCandidateResult, ProcessingError,
-        ReadingIdentificationError, RejectedLcdScreenCandidate,
+        self, DebuggerTrace, LcdCandidateRejection, LcdCandidateScore, LcdScreenCandidate,
+        LcdScreenCandidateResult, ProcessingError, RejectedLcdScreenCandidate,
     },
-    rectangle::get_rectangle_coordinates,
+    rectangle::{get_rectangle_coordinates, get_rectangle_dimensions},
 };
 
-pub(crate) struct LcdScreenExtractor<T: BpmOcrDebugOutputter> {
-    debugger: Arc<T>,
-    debug_session_name: String,
+/// The perspective corrected LCD screen along with where it was found in the image it was extracted from
+pub(crate) struct LcdExtraction {
+    pub birdseye_view: Mat,
+    pub corners: models::RectangleCoordinates,
+    /// The 3x3 matrix mapping the image the LCD was extracted from onto the birdseye view
+    pub perspective_transform: Mat,
 }
 
-impl<T: BpmOcrDebugOutputter> LcdScreenExtractor<T> {
-    pub fn new(debugger: Arc<T>, debug_session_name: &str) -> Self {
-        LcdScreenExtractor {
-            debugger: debugger,
-            debug_session_name: debug_session_name.to_owned(),
-        }
+pub(crate) struct LcdScreenExtractor {
+    config: LcdScreenConfig,
+}
+
+impl LcdScreenExtractor {
+    pub fn new(config: LcdScreenConfig) -> Self {
+        LcdScreenExtractor { config: config }
     }
 
     fn get_lcd_candidate_points(
         self: &Self,
+        image_blurred: &Mat,
         contour: Vector<Point>,
     ) -> Result<LcdScreenCandidateResult, Error> {
         let mut approx_curv_output: Vector<Point> = Vector::new();
 
         let perimeter = arc_length(&contour, true)?;
 
-        approx_poly_dp(&contour, &mut approx_curv_output, 0.02 * perimeter, true)?;
+        approx_poly_dp(
+            &contour,
+            &mut approx_curv_output,
+            self.config.polygon_approximation_epsilon * perimeter,
+            true,
+        )?;
 
         if approx_curv_output.len() == 4 {
             let area = imgproc::contour_area(&approx_curv_output, true)?;
+            let score = self.score_candidate(image_blurred, &approx_curv_output, area)?;
 
             let result = LcdScreenCandidate {
                 coordinates: approx_curv_output,
                 area: area,
                 contour: contour,
+                score: score,
             };
 
             return Ok(LcdScreenCandidateResult::Success(result));
@@ -57,6 +71,90 @@ impl<T: BpmOcrDebugOutputter> LcdScreenExtractor<T> {
         }
     }
 
+    fn score_candidate(
+        self: &Self,
+        image_blurred: &Mat,
+        coordinates: &Vector<Point>,
+        area: f64,
+    ) -> Result<LcdCandidateScore, Error> {
+        let scoring = &self.config.candidate_scoring;
+
+        let (width, height) = get_rectangle_coordinates(coordinates)
+            .map(|corners| get_rectangle_dimensions(&corners))
+            .unwrap_or((0, 0));
+
+        let aspect_ratio = if height > 0 {
+            width as f64 / height as f64
+        } else {
+            0.
+        };
+
+        let image_area = (image_blurred.cols() * image_blurred.rows()) as f64;
+        let image_fraction = area.abs() / image_area;
+
+        let convex = imgproc::is_contour_convex(coordinates)?;
+
+        // A screen with digits on it should have plenty of variation in brightness, unlike a plain button or the casing
+        let bounds = imgproc::bounding_rect(coordinates)?;
+        let contrast = if bounds.width > 0 && bounds.height > 0 {
+            let interior = image_blurred.roi(bounds)?;
+
+            // Only the quadrilateral itself is measured, not the corners of its bounding box
+            let corners: Vector<Point> = coordinates
+                .iter()
+                .map(|point| point - bounds.tl())
+                .collect();
+            let mut mask = Mat::new_rows_cols_with_default(
+                bounds.height,
+                bounds.width,
+                CV_8U,
+                Scalar::all(0.),
+            )?;
+            imgproc::fill_convex_poly_def(&mut mask, &corners, Scalar::all(255.))?;
+
+            let mut mean = Mat::default();
+            let mut standard_deviation = Mat::default();
+            mean_std_dev(&interior, &mut mean, &mut standard_deviation, &mask)?;
+            *standard_deviation.at::<f64>(0)?
+        } else {
+            0.
+        };
+
+        let score = score_in_range(
+            aspect_ratio,
+            scoring.min_aspect_ratio,
+            scoring.max_aspect_ratio,
+        ) * score_in_range(
+            image_fraction,
+            scoring.min_image_fraction,
+            scoring.max_image_fraction,
+        ) * (contrast / scoring.target_contrast).min(1.0);
+
+        Ok(LcdCandidateScore {
+            aspect_ratio,
+            image_fraction,
+            convex,
+            contrast,
+            score,
+        })
+    }
+
+    /// Decides whether a candidate can be discarded without attempting to read any digits from it
+    pub fn rejection_before_reading(
+        self: &Self,
+        candidate: &LcdScreenCandidate,
+    ) -> Option<LcdCandidateRejection> {
+        if !candidate.score.convex {
+            return Some(LcdCandidateRejection::NotConvex);
+        }
+
+        if candidate.score.score < self.config.candidate_scoring.min_score {
+            return Some(LcdCandidateRejection::ScoreTooLow(candidate.score.score));
+        }
+
+        None
+    }
+
     fn partition_candidates(
         self: &Self,
         results: Vec<LcdScreenCandidateResult>,
@@ -74,31 +172,13 @@ impl<T: BpmOcrDebugOutputter> LcdScreenExtractor<T> {
         (lcd_screen_candidates, rejected_screen_candidates)
     }
 
-    fn extract_lcd_birdseye_view(
+    fn extract_lcd_birdseye_view<T: BpmOcrDebugOutputter>(
         self: &Self,
         image: &Mat,
         led_coordinates: models::RectangleCoordinates,
-    ) -> Result<Mat, ProcessingError> {
-        let width_bottom = ((led_coordinates.bottom_right.x - led_coordinates.bottom_left.x)
-            .pow(2)
-            + (led_coordinates.bottom_right.y - led_coordinates.bottom_left.y).pow(2))
-        .isqrt();
-
-        let width_top = ((led_coordinates.top_right.x - led_coordinates.top_left.x).pow(2)
-            + (led_coordinates.top_right.y - led_coordinates.top_left.y).pow(2))
-        .isqrt();
-
-        let max_width = max(width_bottom, width_top);
-
-        let height_bottom = ((led_coordinates.top_right.x - led_coordinates.bottom_right.x).pow(2)
-            + (led_coordinates.top_right.y - led_coordinates.bottom_right.y).pow(2))
-        .isqrt();
-
-        let height_top = ((led_coordinates.top_left.x - led_coordinates.bottom_left.x).pow(2)
-            + (led_coordinates.top_left.y - led_coordinates.bottom_left.y).pow(2))
-        .isqrt();
-
-        let max_height = max(height_bottom, height_top);
+        debugging_session: &DebuggerTrace<T>,
+    ) -> Result<LcdExtraction, ProcessingError> {
+        let (max_width, max_height) = get_rectangle_dimensions(&led_coordinates);
 
         let src_points: Vector<Point2f> = Vector::from_slice(&[
             Point2f::new(
@@ -129,32 +209,39 @@ impl<T: BpmOcrDebugOutputter> LcdScreenExtractor<T> {
         let src_points_mat = Mat::from_slice(src_points.as_slice())?;
         let dest_points_mat = Mat::from_slice(dest_points.as_slice())?;
 
-        let M = get_perspective_transform_def(&src_points_mat, &dest_points_mat)?;
+        let perspective_transform =
+            get_perspective_transform_def(&src_points_mat, &dest_points_mat)?;
 
         let mut dest_image = Mat::default();
 
         warp_perspective_def(
             &image,
             &mut dest_image,
-            &M,
+            &perspective_transform,
             Size::new(max_width, max_height),
         )?;
 
-        self.debugger
-            .debug_after_perspective_transform(&self.debug_session_name, &dest_image)?;
+        debugging_session
+            .debugger
+            .debug_after_perspective_transform(&debugging_session.unique_trace_name, &dest_image)?;
 
-        Ok(dest_image)
+        Ok(LcdExtraction {
+            birdseye_view: dest_image,
+            corners: led_coordinates,
+            perspective_transform,
+        })
     }
 
-    fn get_lcd_candidates(
+    fn get_lcd_candidates<T: BpmOcrDebugOutputter>(
         self: &Self,
         image_blurred: &Mat,
         contours: Vector<Vector<Point>>,
+        debugging_session: &DebuggerTrace<T>,
     ) -> Result<Vec<LcdScreenCandidate>, ProcessingError> {
         let candidate_results: Vec<Result<LcdScreenCandidateResult, Error>> = contours
             .to_vec()
             .into_iter()
-            .map(|points| self.get_lcd_candidate_points(points))
+            .map(|points| self.get_lcd_candidate_points(image_blurred, points))
             .collect();
 
         let candidates_or_error: Result<Vec<LcdScreenCandidateResult>, Error> =
@@ -163,8 +250,8 @@ impl<T: BpmOcrDebugOutputter> LcdScreenExtractor<T> {
         let candidates = candidates_or_error?;
         let (success_candidates, failure_candidates) = self.partition_candidates(candidates);
 
-        let _ = &self.debugger.debug_lcd_contour_candidates(
-            &self.debug_session_name,
+        let _ = &debugging_session.debugger.debug_lcd_contour_candidates(
+            &debugging_session.unique_trace_name,
             &image_blurred,
             &success_candidates,
             failure_candidates,
@@ -173,15 +260,27 @@ impl<T: BpmOcrDebugOutputter> LcdScreenExtractor<T> {
         Ok(success_candidates)
     }
 
-    pub fn extract_lcd(&self, resized_image: &Mat) -> Result<Mat, ProcessingError> {
+    /// Finds every quadrilateral in the image that could be the LCD screen, best candidate first
+    pub fn get_ranked_lcd_candidates<T: BpmOcrDebugOutputter>(
+        &self,
+        resized_image: &Mat,
+        debugging_session: &DebuggerTrace<T>,
+    ) -> Result<Vec<LcdScreenCandidate>, ProcessingError> {
         let mut blurred = Mat::default();
-        imgproc::gaussian_blur_def(&resized_image, &mut blurred, Size::new(5, 5), 0.0)?;
+        let blur_kernel = Size::new(self.config.blur_kernel_size, self.config.blur_kernel_size);
+        imgproc::gaussian_blur_def(&resized_image, &mut blurred, blur_kernel, 0.0)?;
 
         let mut edges = UMat::new_def();
-        imgproc::canny_def(&blurred, &mut edges, 50., 200.)?;
+        imgproc::canny_def(
+            &blurred,
+            &mut edges,
+            self.config.canny_low_threshold,
+            self.config.canny_high_threshold,
+        )?;
 
-        self.debugger
-            .debug_after_canny(&self.debug_session_name, &edges)?;
+        debugging_session
+            .debugger
+            .debug_after_canny(&debugging_session.unique_trace_name, &edges)?;
 
         let mut contours_output: Vector<Vector<Point>> = Vector::new();
         imgproc::find_contours(
@@ -192,19 +291,73 @@ impl<T: BpmOcrDebugOutputter> LcdScreenExtractor<T> {
             Point::new(0, 0),
         )?;
 
-        let mut led_candidates = self.get_lcd_candidates(&blurred, contours_output)?;
-        led_candidates.sort_by(|a1, a2| a1.area.total_cmp(&a2.area));
+        let mut led_candidates =
+            self.get_lcd_candidates(&blurred, contours_output, debugging_session)?;
+
+        // Highest score first, falling back to the smallest area when candidates score the same
+        led_candidates.sort_by(|a1, a2| {
+            a2.score
+                .score
+                .total_cmp(&a1.score.score)
+                .then(a1.area.abs().total_cmp(&a2.area.abs()))
+        });
 
-        let best_candidate_led: &LcdScreenCandidate = led_candidates.get(0).ok_or_else(|| {
-            ProcessingError::AppError(ReadingIdentificationError::CouldNotIdentityLCDCandidate)
-        })?;
+        Ok(led_candidates)
+    }
 
-        let lcd_coordinates = get_rectangle_coordinates(&best_candidate_led.coordinates).ok_or(
+    pub fn extract_lcd<T: BpmOcrDebugOutputter>(
+        &self,
+        resized_image: &Mat,
+        candidate: &LcdScreenCandidate,
+        debugging_session: &DebuggerTrace<T>,
+    ) -> Result<LcdExtraction, ProcessingError> {
+        let lcd_coordinates = get_rectangle_coordinates(&candidate.coordinates).ok_or(
             ProcessingError::AppError(models::ReadingIdentificationError::InternalError(
                 "Internal error: LCD candidate did not have 4 points as expected",
             )),
         )?;
 
-        self.extract_lcd_birdseye_view(&resized_image, lcd_coordinates)
+        self.extract_lcd_birdseye_view(&resized_image, lcd_coordinates, debugging_session)
+    }
+}
+
+/// 1.0 when the value is within the range, falling away towards 0.0 the further outside of it the value is
+fn score_in_range(value: f64, min: f64, max: f64) -> f64 {
+    if value < min {
+        (value / min).max(0.)
+    } else if value > max {
+        max / value
+    } else {
+        1.
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opencv::core::Rect2i;
+
+    #[test]
+    fn test_contrast_measured_inside_candidate() {
+        let mut image = Mat::new_rows_cols_with_default(100, 100, CV_8U, Scalar::all(0.)).unwrap();
+
+        // Bright squares in each corner of the bounding box, all outside the diamond
+        for (x, y) in [(10, 10), (75, 10), (10, 75), (75, 75)] {
+            imgproc::rectangle_def(&mut image, Rect2i::new(x, y, 15, 15), Scalar::all(255.))
+                .unwrap();
+        }
+
+        let diamond: Vector<Point> = Vector::from_slice(&[
+            Point::new(50, 10),
+            Point::new(90, 50),
+            Point::new(50, 90),
+            Point::new(10, 50),
+        ]);
+        let area = imgproc::contour_area(&diamond, true).unwrap();
+
+        let extractor = LcdScreenExtractor::new(LcdScreenConfig::default());
+        let score = extractor.score_candidate(&image, &diamond, area).unwrap();
+
+        assert_eq!(score.contrast, 0.);
     }
 }
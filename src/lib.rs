@@ -1,69 +1,408 @@
-use std::sync::Arc;
-
-use opencv::core::{Mat, Size, Vector};
+use opencv::core::{CV_8U, Mat, MatTraitConst, Point, Point2f, Rect2i, Size, Vector};
 use opencv::imgcodecs::ImreadModes;
 use opencv::{imgcodecs, imgproc};
+use std::sync::Arc;
 
+use crate::classifier::{ClassifierMode, DigitClassifier};
+use crate::config::ExtractorConfig;
 use crate::debug::BpmOcrDebugOutputter;
 use crate::lcd_number_extractor::LcdNumberExtractor;
-use crate::lcd_screen_extractor::LcdScreenExtractor;
-use crate::models::{BloodPressureReading, DebuggerTrace, ProcessingError};
+use crate::lcd_screen_extractor::{LcdExtraction, LcdScreenExtractor};
+use crate::models::{
+    BloodPressureReading, DebuggerTrace, LcdCandidateRejection, ProcessingError, Quadrilateral,
+    ReadingIdentificationError, ReadingLocations, ReadingReport, ReadingWithConfidence,
+    ScreenReading,
+};
+pub mod batch;
+pub mod classifier;
+pub mod config;
+pub mod dataset;
+mod date_time_extractor;
 pub mod debug;
 mod digit_extractor;
+pub mod font;
 mod lcd_number_extractor;
 mod lcd_screen_extractor;
 pub mod models;
+pub mod profile;
 mod rectangle;
+pub mod seven_segment;
+#[cfg(test)]
+mod test_drawing;
+mod validation;
 
-pub struct BloodPressureReadingExtractor<T: BpmOcrDebugOutputter> {
-    screen_extractor: LcdScreenExtractor<T>,
-    screen_number_extractor: LcdNumberExtractor<T>,
-    debugging_session: DebuggerTrace<T>,
+/// Extracts blood pressure readings from photos. An extractor holds no per-image state, so a single
+/// instance can be shared between threads and used for any number of images.
+pub struct BloodPressureReadingExtractor {
+    screen_extractor: LcdScreenExtractor,
+    screen_number_extractor: LcdNumberExtractor,
+    config: ExtractorConfig,
 }
 
-impl<T: BpmOcrDebugOutputter> BloodPressureReadingExtractor<T> {
-    pub fn new(debugger_session: DebuggerTrace<T>) -> Self {
-        let screen_extractor = LcdScreenExtractor::new(
-            Arc::clone(&debugger_session.debugger),
-            &debugger_session.unique_trace_name,
-        );
-        let screen_number_extractor = LcdNumberExtractor::new(
-            Arc::clone(&debugger_session.debugger),
-            &debugger_session.unique_trace_name,
-        );
+impl Default for BloodPressureReadingExtractor {
+    fn default() -> Self {
+        Self::new(ExtractorConfig::default())
+    }
+}
+
+impl BloodPressureReadingExtractor {
+    pub fn new(config: ExtractorConfig) -> Self {
+        let screen_extractor = LcdScreenExtractor::new(config.lcd_screen.clone());
+        let screen_number_extractor =
+            LcdNumberExtractor::new(config.digits.clone(), config.segments.clone());
 
         BloodPressureReadingExtractor {
             screen_extractor,
             screen_number_extractor,
-            debugging_session: debugger_session,
+            config,
         }
     }
 
-    fn process_image(self: &Self, image: &Mat) -> Result<BloodPressureReading, ProcessingError> {
-        self.debugging_session
+    /// Builds an extractor, first checking the config, its profile and font can be worked with
+    pub fn try_new(config: ExtractorConfig) -> Result<Self, ProcessingError> {
+        validate_config(&config)?;
+
+        Ok(Self::new(config))
+    }
+
+    /// Reads digits with a trained classifier, either instead of the segment rules or for the
+    /// digits they cannot read
+    pub fn with_classifier(
+        mut self,
+        classifier: Arc<DigitClassifier>,
+        mode: ClassifierMode,
+    ) -> Self {
+        self.screen_number_extractor = self
+            .screen_number_extractor
+            .with_classifier(classifier, mode);
+        self
+    }
+
+    pub fn config(self: &Self) -> &ExtractorConfig {
+        &self.config
+    }
+
+    /// Attempts to extract a blood pressure reading from an image of a blood pressure monitor screen
+    /// * `image` - the image, in BGR, BGRA or grayscale with 8 bits per channel
+    /// * `debugging_session` - the debugger trace session to output debug images for this image with
+    pub fn extract<T: BpmOcrDebugOutputter>(
+        self: &Self,
+        image: &Mat,
+        debugging_session: &DebuggerTrace<T>,
+    ) -> Result<BloodPressureReading, ProcessingError> {
+        self.extract_report(image, debugging_session)
+            .map(|report| report.reading.reading())
+    }
+
+    /// Attempts to extract a blood pressure reading from an image of a blood pressure monitor screen,
+    /// along with the location of the LCD screen and each digit within the image
+    /// * `image` - the image, in BGR, BGRA or grayscale with 8 bits per channel
+    /// * `debugging_session` - the debugger trace session to output debug images for this image with
+    pub fn extract_report<T: BpmOcrDebugOutputter>(
+        self: &Self,
+        image: &Mat,
+        debugging_session: &DebuggerTrace<T>,
+    ) -> Result<ReadingReport, ProcessingError> {
+        let grayscale = to_grayscale(image)?;
+
+        self.process_image(&grayscale, debugging_session)
+    }
+
+    /// Finds where the digits of each field are on the best LCD screen candidate they can be found
+    /// on that `accept` agrees with, along with its highlighted screen. None of the digits are read.
+    pub(crate) fn locate_digits<T: BpmOcrDebugOutputter>(
+        self: &Self,
+        image: &Mat,
+        debugging_session: &DebuggerTrace<T>,
+        accept: impl Fn(&ReadingLocations) -> bool,
+    ) -> Result<(Mat, ReadingLocations), ProcessingError> {
+        validate_config(&self.config)?;
+
+        let grayscale = to_grayscale(image)?;
+        let resized_image = self.normalize_image(&grayscale)?;
+
+        let (_, located_reading) =
+            self.read_first_lcd_candidate(&resized_image, debugging_session, |lcd| {
+                let (highlighted_digits, locations) = self.screen_number_extractor.locate_reading(
+                    &lcd.birdseye_view,
+                    &self.config.profile,
+                    debugging_session,
+                )?;
+
+                if !accept(&locations) {
+                    return Err(ProcessingError::AppError(
+                        ReadingIdentificationError::CouldNotIdentifyReadings,
+                    ));
+                }
+
+                Ok((highlighted_digits, locations))
+            })?;
+
+        Ok(located_reading)
+    }
+
+    fn process_image<T: BpmOcrDebugOutputter>(
+        self: &Self,
+        image: &Mat,
+        debugging_session: &DebuggerTrace<T>,
+    ) -> Result<ReadingReport, ProcessingError> {
+        debugging_session
             .debugger
-            .debug_original_picture(&self.debugging_session.unique_trace_name, &image)?;
+            .debug_original_picture(&debugging_session.unique_trace_name, &image)?;
+
+        validate_config(&self.config)?;
+
+        let original_size = image.size()?;
+
+        let resized_image = self.normalize_image(image)?;
+
+        let (lcd, screen_reading) =
+            self.read_best_lcd_candidate(&resized_image, debugging_session)?;
+
+        self.build_report(original_size, resized_image.size()?, lcd, screen_reading)
+    }
+
+    /// Resizes the image so that its longest edge is the configured length, keeping its aspect ratio
+    fn normalize_image(self: &Self, image: &Mat) -> Result<Mat, ProcessingError> {
+        let normalization = &self.config.normalization;
+        let size = image.size()?;
+
+        let scale = normalization.long_edge as f64 / size.width.max(size.height) as f64;
+        let normalized_size = Size::new(
+            ((size.width as f64 * scale).round() as i32).max(1),
+            ((size.height as f64 * scale).round() as i32).max(1),
+        );
 
         let mut resized_image = Mat::default();
 
-        let interpolation: i32 = 0;
         imgproc::resize(
             &image,
             &mut resized_image,
-            Size::new(800, 800),
+            normalized_size,
             0.,
             0.,
-            interpolation,
+            normalization.interpolation,
         )?;
 
-        let birdseye_lcd_only = self.screen_extractor.extract_lcd(&resized_image)?;
+        Ok(resized_image)
+    }
 
-        let reading = self
-            .screen_number_extractor
-            .extract_reading(&birdseye_lcd_only)?;
+    /// Tries to read the digits from each LCD screen candidate in turn, best candidate first, until
+    /// one of them gives a full, plausible reading
+    fn read_best_lcd_candidate<T: BpmOcrDebugOutputter>(
+        self: &Self,
+        resized_image: &Mat,
+        debugging_session: &DebuggerTrace<T>,
+    ) -> Result<(LcdExtraction, ScreenReading), ProcessingError> {
+        self.read_first_lcd_candidate(resized_image, debugging_session, |lcd| {
+            let screen_reading = self.screen_number_extractor.extract_reading(
+                &lcd.birdseye_view,
+                &self.config.profile,
+                debugging_session,
+            )?;
 
-        Ok(reading)
+            // A reading that could not possibly be right means we are most likely looking at something other than the screen
+            let plain_reading = screen_reading.reading.reading();
+
+            validation::validate_reading(&plain_reading, &self.config.validation).map_err(
+                |rule| {
+                    ProcessingError::AppError(ReadingIdentificationError::ImplausibleReading {
+                        rule,
+                        reading: plain_reading.clone(),
+                    })
+                },
+            )?;
+
+            Ok(screen_reading)
+        })
+    }
+
+    /// Extracts each LCD screen candidate in turn, best candidate first, until `read` succeeds on one
+    /// of them. A candidate that fails, including because OpenCV could not process it, is rejected
+    /// and the next one is tried.
+    fn read_first_lcd_candidate<T: BpmOcrDebugOutputter, R>(
+        self: &Self,
+        resized_image: &Mat,
+        debugging_session: &DebuggerTrace<T>,
+        read: impl Fn(&LcdExtraction) -> Result<R, ProcessingError>,
+    ) -> Result<(LcdExtraction, R), ProcessingError> {
+        let trace_name = &debugging_session.unique_trace_name;
+        let debugger = &debugging_session.debugger;
+
+        let candidates = self
+            .screen_extractor
+            .get_ranked_lcd_candidates(resized_image, debugging_session)?;
+
+        let mut failures = CandidateFailures::default();
+
+        for (rank, candidate) in candidates.iter().enumerate() {
+            if let Some(rejection) = self.screen_extractor.rejection_before_reading(candidate) {
+                debugger.debug_rejected_lcd_candidate(
+                    trace_name,
+                    rank,
+                    resized_image,
+                    candidate,
+                    &rejection,
+                )?;
+                continue;
+            }
+
+            let result = self
+                .screen_extractor
+                .extract_lcd(resized_image, candidate, debugging_session)
+                .and_then(|lcd| read(&lcd).map(|value| (lcd, value)));
+
+            match result {
+                Ok(read_candidate) => return Ok(read_candidate),
+                Err(error) => {
+                    let rejection = failures.record(error);
+
+                    debugger.debug_rejected_lcd_candidate(
+                        trace_name,
+                        rank,
+                        resized_image,
+                        candidate,
+                        &rejection,
+                    )?;
+                }
+            }
+        }
+
+        Err(failures.into_error())
     }
+
+    fn build_report(
+        self: &Self,
+        original_size: Size,
+        resized_size: Size,
+        lcd: LcdExtraction,
+        screen_reading: ScreenReading,
+    ) -> Result<ReadingReport, ProcessingError> {
+        let locations = screen_reading.locations;
+
+        // Everything up until now has been measured in the resized image, so scale it back up to the original photo
+        let scale_x = resized_size.width as f64 / original_size.width as f64;
+        let scale_y = resized_size.height as f64 / original_size.height as f64;
+
+        let perspective_transform = rectangle::scale_homography_source(
+            &rectangle::homography_from_mat(&lcd.perspective_transform)?,
+            scale_x,
+            scale_y,
+        );
+
+        let birdseye_to_original = rectangle::invert_homography(&perspective_transform).ok_or(
+            ProcessingError::AppError(ReadingIdentificationError::InternalError(
+                "Internal error: the perspective transform could not be inverted",
+            )),
+        )?;
+
+        let to_original = |point: Point| {
+            Point2f::new(
+                (point.x as f64 / scale_x) as f32,
+                (point.y as f64 / scale_y) as f32,
+            )
+        };
+
+        let to_original_digits = |digits: &Vec<Rect2i>| -> Vec<Quadrilateral> {
+            digits
+                .iter()
+                .map(|digit| rectangle::map_rectangle(&birdseye_to_original, *digit))
+                .collect()
+        };
+
+        Ok(ReadingReport {
+            reading: screen_reading.reading,
+            profile_name: locations.profile_name.clone(),
+            indicators: screen_reading.indicators,
+            date_time: screen_reading.date_time,
+            lcd_corners: Quadrilateral {
+                top_left: to_original(lcd.corners.top_left),
+                top_right: to_original(lcd.corners.top_right),
+                bottom_right: to_original(lcd.corners.bottom_right),
+                bottom_left: to_original(lcd.corners.bottom_left),
+            },
+            perspective_transform,
+            systolic_digits: to_original_digits(&locations.systolic_region),
+            diastolic_digits: to_original_digits(&locations.diastolic_region),
+            pulse_digits: to_original_digits(&locations.pulse_region),
+        })
+    }
+}
+
+/// Why each LCD screen candidate could not be read, so that the most useful reason can be reported
+/// if none of them can be
+#[derive(Default)]
+struct CandidateFailures {
+    furthest: Option<ReadingIdentificationError>,
+    first_library_error: Option<opencv::Error>,
+}
+
+impl CandidateFailures {
+    /// Records why a candidate could not be read, returning the rejection to debug it with
+    fn record(self: &mut Self, error: ProcessingError) -> LcdCandidateRejection {
+        match error {
+            ProcessingError::AppError(error) => {
+                let rejection = LcdCandidateRejection::ReadingFailed(error.clone());
+
+                let got_further = self
+                    .furthest
+                    .as_ref()
+                    .map(|furthest| error.stage_reached() > furthest.stage_reached())
+                    .unwrap_or(true);
+
+                if got_further {
+                    self.furthest = Some(error);
+                }
+
+                rejection
+            }
+            ProcessingError::ImageDetectionLibraryError(error) => {
+                let rejection = LcdCandidateRejection::ImageProcessingFailed(error.clone());
+                self.first_library_error.get_or_insert(error);
+                rejection
+            }
+        }
+    }
+
+    /// Why the candidate that got furthest through the pipeline could not be read, as that is most
+    /// likely to be the screen. Ties go to the highest ranked candidate. An OpenCV error is only
+    /// reported when no candidate got as far as reading any digits.
+    fn into_error(self: Self) -> ProcessingError {
+        match (self.furthest, self.first_library_error) {
+            (Some(error), _) => ProcessingError::AppError(error),
+            (None, Some(error)) => ProcessingError::ImageDetectionLibraryError(error),
+            (None, None) => {
+                ProcessingError::AppError(ReadingIdentificationError::CouldNotIdentityLCDCandidate)
+            }
+        }
+    }
+}
+
+pub(crate) fn read_image_file(filename: &str) -> Result<Mat, ProcessingError> {
+    let gray_scale_mode: i32 = ImreadModes::IMREAD_GRAYSCALE.into();
+    let image = imgcodecs::imread(filename, gray_scale_mode)?;
+
+    // OpenCV gives back an empty image rather than an error when the file is missing or not an image
+    if image.empty() {
+        return Err(ProcessingError::AppError(
+            ReadingIdentificationError::UnsupportedImageFormat,
+        ));
+    }
+
+    Ok(image)
+}
+
+pub(crate) fn decode_image_buffer(file_contents: &[u8]) -> Result<Mat, ProcessingError> {
+    let contents = Vector::from_slice(file_contents);
+    let image = imgcodecs::imdecode(&contents, ImreadModes::IMREAD_GRAYSCALE.into())?;
+
+    if image.empty() {
+        return Err(ProcessingError::AppError(
+            ReadingIdentificationError::UnsupportedImageFormat,
+        ));
+    }
+
+    Ok(image)
 }
 
 /// Attempts to extract a blood pressure reading from a photo file of a blood pressure monitor screen
@@ -73,12 +412,51 @@ pub fn get_reading_from_file<T: BpmOcrDebugOutputter>(
     filename: &str,
     debugger: DebuggerTrace<T>,
 ) -> Result<BloodPressureReading, ProcessingError> {
-    let extractor: BloodPressureReadingExtractor<T> = BloodPressureReadingExtractor::new(debugger);
+    get_reading_from_file_with_config(filename, debugger, ExtractorConfig::default())
+}
 
-    let gray_scale_mode: i32 = ImreadModes::IMREAD_GRAYSCALE.into();
-    let image = imgcodecs::imread(filename, gray_scale_mode)?;
+/// Attempts to extract a blood pressure reading from a photo file of a blood pressure monitor screen
+/// using the given pipeline tuning parameters
+/// * `filename` - the path to the photo file
+/// * `debugger` - the debugger trace session to output debug images with
+/// * `config` - the tuning parameters for each stage of the pipeline
+pub fn get_reading_from_file_with_config<T: BpmOcrDebugOutputter>(
+    filename: &str,
+    debugger: DebuggerTrace<T>,
+    config: ExtractorConfig,
+) -> Result<BloodPressureReading, ProcessingError> {
+    get_reading_with_confidence_from_file(filename, debugger, config)
+        .map(|reading| reading.reading())
+}
 
-    extractor.process_image(&image)
+/// Attempts to extract a blood pressure reading from a photo file of a blood pressure monitor screen,
+/// along with how confident we are in each digit and field of the reading
+/// * `filename` - the path to the photo file
+/// * `debugger` - the debugger trace session to output debug images with
+/// * `config` - the tuning parameters for each stage of the pipeline
+pub fn get_reading_with_confidence_from_file<T: BpmOcrDebugOutputter>(
+    filename: &str,
+    debugger: DebuggerTrace<T>,
+    config: ExtractorConfig,
+) -> Result<ReadingWithConfidence, ProcessingError> {
+    get_reading_report_from_file(filename, debugger, config).map(|report| report.reading)
+}
+
+/// Attempts to extract a blood pressure reading from a photo file of a blood pressure monitor screen,
+/// along with the location of the LCD screen and each digit within the photo
+/// * `filename` - the path to the photo file
+/// * `debugger` - the debugger trace session to output debug images with
+/// * `config` - the tuning parameters for each stage of the pipeline
+pub fn get_reading_report_from_file<T: BpmOcrDebugOutputter>(
+    filename: &str,
+    debugger: DebuggerTrace<T>,
+    config: ExtractorConfig,
+) -> Result<ReadingReport, ProcessingError> {
+    let extractor = BloodPressureReadingExtractor::try_new(config)?;
+
+    let image = read_image_file(filename)?;
+
+    extractor.process_image(&image, &debugger)
 }
 
 /// Attempts to extract a blood pressure reading from a byte buffer containing a photo file of a blood pressure monitor screen
@@ -88,18 +466,129 @@ pub fn get_reading_from_buffer<T: BpmOcrDebugOutputter>(
     file_contents: Vec<u8>,
     debugger: DebuggerTrace<T>,
 ) -> Result<BloodPressureReading, ProcessingError> {
-    let extractor: BloodPressureReadingExtractor<T> = BloodPressureReadingExtractor::new(debugger);
+    get_reading_from_buffer_with_config(file_contents, debugger, ExtractorConfig::default())
+}
 
-    let contents = Vector::from_slice(&file_contents);
-    let image = imgcodecs::imdecode(&contents, ImreadModes::IMREAD_GRAYSCALE.into())?;
+/// Attempts to extract a blood pressure reading from a byte buffer containing a photo file of a blood pressure monitor screen
+/// using the given pipeline tuning parameters
+/// * `filename` - the byte buffer with the photo file
+/// * `debugger` - the debugger trace session to output debug images with
+/// * `config` - the tuning parameters for each stage of the pipeline
+pub fn get_reading_from_buffer_with_config<T: BpmOcrDebugOutputter>(
+    file_contents: Vec<u8>,
+    debugger: DebuggerTrace<T>,
+    config: ExtractorConfig,
+) -> Result<BloodPressureReading, ProcessingError> {
+    get_reading_with_confidence_from_buffer(file_contents, debugger, config)
+        .map(|reading| reading.reading())
+}
+
+/// Attempts to extract a blood pressure reading from a byte buffer containing a photo file of a blood pressure monitor screen,
+/// along with how confident we are in each digit and field of the reading
+/// * `filename` - the byte buffer with the photo file
+/// * `debugger` - the debugger trace session to output debug images with
+/// * `config` - the tuning parameters for each stage of the pipeline
+pub fn get_reading_with_confidence_from_buffer<T: BpmOcrDebugOutputter>(
+    file_contents: Vec<u8>,
+    debugger: DebuggerTrace<T>,
+    config: ExtractorConfig,
+) -> Result<ReadingWithConfidence, ProcessingError> {
+    get_reading_report_from_buffer(file_contents, debugger, config).map(|report| report.reading)
+}
+
+/// Attempts to extract a blood pressure reading from a byte buffer containing a photo file of a blood pressure monitor screen,
+/// along with the location of the LCD screen and each digit within the photo
+/// * `filename` - the byte buffer with the photo file
+/// * `debugger` - the debugger trace session to output debug images with
+/// * `config` - the tuning parameters for each stage of the pipeline
+pub fn get_reading_report_from_buffer<T: BpmOcrDebugOutputter>(
+    file_contents: Vec<u8>,
+    debugger: DebuggerTrace<T>,
+    config: ExtractorConfig,
+) -> Result<ReadingReport, ProcessingError> {
+    let extractor = BloodPressureReadingExtractor::try_new(config)?;
+
+    let image = decode_image_buffer(&file_contents)?;
+
+    extractor.process_image(&image, &debugger)
+}
+
+/// Checks the tuning parameters, the monitor profiles and the segment font can all be worked with
+fn validate_config(config: &ExtractorConfig) -> Result<(), ProcessingError> {
+    config.validate().map_err(|message| {
+        ProcessingError::AppError(ReadingIdentificationError::InvalidConfig(message))
+    })?;
+
+    config.profile.validate().map_err(|message| {
+        ProcessingError::AppError(ReadingIdentificationError::InvalidMonitorProfile(message))
+    })?;
 
-    extractor.process_image(&image)
+    config.segments.font.validate().map_err(|message| {
+        ProcessingError::AppError(ReadingIdentificationError::InvalidSegmentFont(message))
+    })
+}
+
+/// Converts a BGR, BGRA or grayscale image with 8 bits per channel into the grayscale image the pipeline works on
+fn to_grayscale(image: &Mat) -> Result<Mat, ProcessingError> {
+    if image.empty() || image.depth() != CV_8U {
+        return Err(ProcessingError::AppError(
+            ReadingIdentificationError::UnsupportedImageFormat,
+        ));
+    }
+
+    let conversion = match image.channels() {
+        1 => return Ok(image.try_clone()?),
+        3 => imgproc::COLOR_BGR2GRAY,
+        4 => imgproc::COLOR_BGRA2GRAY,
+        _ => {
+            return Err(ProcessingError::AppError(
+                ReadingIdentificationError::UnsupportedImageFormat,
+            ));
+        }
+    };
+
+    let mut grayscale = Mat::default();
+    imgproc::cvt_color_def(image, &mut grayscale, conversion)?;
+
+    Ok(grayscale)
+}
+
+/// Attempts to extract a blood pressure reading from an already decoded image of a blood pressure monitor screen
+/// * `image` - the image, in BGR, BGRA or grayscale with 8 bits per channel
+/// * `debugger` - the debugger trace session to output debug images with
+pub fn get_reading_from_mat<T: BpmOcrDebugOutputter>(
+    image: &Mat,
+    debugger: DebuggerTrace<T>,
+) -> Result<BloodPressureReading, ProcessingError> {
+    get_reading_report_from_mat(image, debugger, ExtractorConfig::default())
+        .map(|report| report.reading.reading())
+}
+
+/// Attempts to extract a blood pressure reading from an already decoded image of a blood pressure monitor screen,
+/// along with the location of the LCD screen and each digit within the image
+/// * `image` - the image, in BGR, BGRA or grayscale with 8 bits per channel
+/// * `debugger` - the debugger trace session to output debug images with
+/// * `config` - the tuning parameters for each stage of the pipeline
+pub fn get_reading_report_from_mat<T: BpmOcrDebugOutputter>(
+    image: &Mat,
+    debugger: DebuggerTrace<T>,
+    config: ExtractorConfig,
+) -> Result<ReadingReport, ProcessingError> {
+    BloodPressureReadingExtractor::try_new(config)?.extract_report(image, &debugger)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::SegmentSampling;
     use crate::debug::TempFolderDebugger;
+    use crate::font::SegmentFont;
+    use crate::models::{Indicator, PlausibilityRule, PressureUnit};
+    use crate::profile::{
+        DigitCount, FieldLayout, FieldRole, IndicatorLayout, MonitorProfile, ProfileSelection,
+        RelativeRegion, RowLayout,
+    };
+    use crate::seven_segment::SevenSegmentReader;
 
     #[test]
     fn test_success_photo_at_angle() {
@@ -154,4 +643,525 @@ mod tests {
 
         assert_eq!(result, expected_result);
     }
+
+    #[test]
+    fn test_confidence_topdown_photo() {
+        let debug_session: DebuggerTrace<TempFolderDebugger> =
+            DebuggerTrace::temp_folder_session("test_confidence_topdown_photo");
+
+        let testfile = Vec::from(include_bytes!("./test_resources/example_top_down.jpg"));
+
+        let result = get_reading_with_confidence_from_buffer(
+            testfile,
+            debug_session,
+            ExtractorConfig::default(),
+        )
+        .unwrap();
+
+        assert_eq!(result.systolic.digits.len(), 3);
+        assert_eq!(result.diastolic.digits.len(), 2);
+        assert_eq!(result.pulse.digits.len(), 2);
+        assert!((0.0..=1.0).contains(&result.confidence()));
+        assert_eq!(
+            result.reading(),
+            BloodPressureReading {
+                systolic: 131,
+                diastolic: 88,
+                pulse: 77,
+            }
+        );
+    }
+
+    #[test]
+    fn test_report_digits_inside_lcd() {
+        let debug_session: DebuggerTrace<TempFolderDebugger> =
+            DebuggerTrace::temp_folder_session("test_report_digits_inside_lcd");
+
+        let testfile = Vec::from(include_bytes!("./test_resources/example_at_angle.jpg"));
+
+        let report =
+            get_reading_report_from_buffer(testfile, debug_session, ExtractorConfig::default())
+                .unwrap();
+
+        // Detecting the profile is opt in, so every screen is read as stacked by default
+        assert_eq!(report.profile_name, "stacked");
+        assert_eq!(report.systolic_digits.len(), 3);
+        assert_eq!(report.diastolic_digits.len(), 2);
+        assert_eq!(report.pulse_digits.len(), 2);
+
+        let lcd = report.lcd_corners;
+        let min_x = lcd.top_left.x.min(lcd.bottom_left.x);
+        let max_x = lcd.top_right.x.max(lcd.bottom_right.x);
+        let min_y = lcd.top_left.y.min(lcd.top_right.y);
+        let max_y = lcd.bottom_left.y.max(lcd.bottom_right.y);
+
+        for digit in report
+            .systolic_digits
+            .iter()
+            .chain(report.pulse_digits.iter())
+        {
+            let centre_x = (digit.top_left.x + digit.bottom_right.x) / 2.;
+            let centre_y = (digit.top_left.y + digit.bottom_right.y) / 2.;
+
+            assert!(centre_x > min_x && centre_x < max_x);
+            assert!(centre_y > min_y && centre_y < max_y);
+        }
+    }
+
+    #[test]
+    fn test_success_colour_mat() {
+        let debug_session: DebuggerTrace<TempFolderDebugger> =
+            DebuggerTrace::temp_folder_session("test_success_colour_mat");
+
+        let testfile = Vector::from_slice(include_bytes!("./test_resources/example_top_down.jpg"));
+        let image = imgcodecs::imdecode(&testfile, ImreadModes::IMREAD_COLOR.into()).unwrap();
+
+        let expected_result = BloodPressureReading {
+            systolic: 131,
+            diastolic: 88,
+            pulse: 77,
+        };
+
+        let result = get_reading_from_mat(&image, debug_session).unwrap();
+
+        assert_eq!(result, expected_result);
+    }
+
+    #[test]
+    fn test_buffer_that_is_not_an_image_rejected() {
+        let debug_session: DebuggerTrace<TempFolderDebugger> =
+            DebuggerTrace::temp_folder_session("test_buffer_that_is_not_an_image_rejected");
+
+        let result = get_reading_from_buffer(b"not an image at all".to_vec(), debug_session);
+
+        assert!(matches!(
+            result,
+            Err(ProcessingError::AppError(
+                ReadingIdentificationError::UnsupportedImageFormat
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_missing_file_rejected() {
+        let debug_session: DebuggerTrace<TempFolderDebugger> =
+            DebuggerTrace::temp_folder_session("test_missing_file_rejected");
+
+        let result = get_reading_from_file("./test_resources/no_such_photo.jpg", debug_session);
+
+        assert!(matches!(
+            result,
+            Err(ProcessingError::AppError(
+                ReadingIdentificationError::UnsupportedImageFormat
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_invalid_config_rejected() {
+        let mut config = ExtractorConfig::default();
+        config.lcd_screen.blur_kernel_size = 4;
+
+        assert!(matches!(
+            BloodPressureReadingExtractor::try_new(config),
+            Err(ProcessingError::AppError(
+                ReadingIdentificationError::InvalidConfig(_)
+            ))
+        ));
+
+        let mut config = ExtractorConfig::default();
+        config.digits.min_digit_height_ratio = 1.5;
+
+        let debug_session: DebuggerTrace<TempFolderDebugger> =
+            DebuggerTrace::temp_folder_session("test_invalid_config_rejected");
+
+        let testfile = Vec::from(include_bytes!("./test_resources/example_top_down.jpg"));
+        let result = get_reading_from_buffer_with_config(testfile, debug_session, config);
+
+        assert!(matches!(
+            result,
+            Err(ProcessingError::AppError(
+                ReadingIdentificationError::InvalidConfig(_)
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_extractor_shared_between_threads() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<BloodPressureReadingExtractor>();
+
+        let extractor = BloodPressureReadingExtractor::default();
+
+        let expected_result = BloodPressureReading {
+            systolic: 133,
+            diastolic: 93,
+            pulse: 65,
+        };
+
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..2)
+                .map(|index| {
+                    let extractor = &extractor;
+
+                    scope.spawn(move || {
+                        let testfile = Vector::from_slice(include_bytes!(
+                            "./test_resources/example_at_angle.jpg"
+                        ));
+                        let image =
+                            imgcodecs::imdecode(&testfile, ImreadModes::IMREAD_GRAYSCALE.into())
+                                .unwrap();
+
+                        let debug_session = DebuggerTrace::temp_folder_session(&format!(
+                            "test_extractor_shared_between_threads_{}",
+                            index
+                        ));
+
+                        extractor.extract(&image, &debug_session).unwrap()
+                    })
+                })
+                .collect();
+
+            for handle in handles {
+                assert_eq!(handle.join().unwrap(), expected_result);
+            }
+        });
+    }
+
+    #[test]
+    fn test_implausible_reading_rejected() {
+        let debug_session: DebuggerTrace<TempFolderDebugger> =
+            DebuggerTrace::temp_folder_session("test_implausible_reading_rejected");
+
+        let testfile = Vec::from(include_bytes!("./test_resources/example_top_down.jpg"));
+
+        // The photo reads 131/88, so a maximum systolic pressure of 120 can never be satisfied
+        let mut config = ExtractorConfig::default();
+        config.validation.systolic.max = 120;
+
+        let result = get_reading_from_buffer_with_config(testfile, debug_session, config);
+
+        assert!(matches!(
+            result,
+            Err(ProcessingError::AppError(
+                ReadingIdentificationError::ImplausibleReading {
+                    rule: PlausibilityRule::SystolicOutOfRange,
+                    ..
+                }
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_lcd_candidates_ranked_best_first() {
+        let debug_session: DebuggerTrace<TempFolderDebugger> =
+            DebuggerTrace::temp_folder_session("test_lcd_candidates_ranked_best_first");
+
+        let testfile = Vec::from(include_bytes!("./test_resources/contour_candidates.jpeg"));
+        let image = decode_image_buffer(&testfile).unwrap();
+
+        let extractor = BloodPressureReadingExtractor::default();
+        let resized_image = extractor.normalize_image(&image).unwrap();
+
+        let candidates = extractor
+            .screen_extractor
+            .get_ranked_lcd_candidates(&resized_image, &debug_session)
+            .unwrap();
+
+        assert!(!candidates.is_empty());
+        assert!(
+            candidates
+                .windows(2)
+                .all(|pair| pair[0].score.score >= pair[1].score.score)
+        );
+    }
+
+    #[test]
+    fn test_candidate_failures_report_furthest_candidate() {
+        let library_error = || opencv::Error::new(opencv::core::StsError, "warp failed");
+
+        // A candidate OpenCV could not process does not stop the others being tried, and is only
+        // reported when nothing else got further
+        let mut failures = CandidateFailures::default();
+        let rejection =
+            failures.record(ProcessingError::ImageDetectionLibraryError(library_error()));
+        assert_eq!(rejection.name(), "image_processing_failed");
+        assert!(matches!(
+            failures.into_error(),
+            ProcessingError::ImageDetectionLibraryError(_)
+        ));
+
+        let mut failures = CandidateFailures::default();
+        failures.record(ProcessingError::AppError(
+            ReadingIdentificationError::UnexpectedNumberOfRows {
+                expected: 3,
+                found: 1,
+            },
+        ));
+        failures.record(ProcessingError::ImageDetectionLibraryError(library_error()));
+        failures.record(ProcessingError::AppError(
+            ReadingIdentificationError::DeviceReportedError {
+                code: "Err".to_owned(),
+            },
+        ));
+        failures.record(ProcessingError::AppError(
+            ReadingIdentificationError::UnexpectedNumberOfRows {
+                expected: 3,
+                found: 2,
+            },
+        ));
+        assert!(matches!(
+            failures.into_error(),
+            ProcessingError::AppError(ReadingIdentificationError::DeviceReportedError { .. })
+        ));
+
+        assert!(matches!(
+            CandidateFailures::default().into_error(),
+            ProcessingError::AppError(ReadingIdentificationError::CouldNotIdentityLCDCandidate)
+        ));
+    }
+
+    #[test]
+    fn test_custom_profile_assigns_fields_by_row() {
+        let debug_session: DebuggerTrace<TempFolderDebugger> =
+            DebuggerTrace::temp_folder_session("test_custom_profile_assigns_fields_by_row");
+
+        let testfile = Vec::from(include_bytes!("./test_resources/example_top_down.jpg"));
+
+        // The same three stacked rows as the photo, but read from the bottom up
+        let mut config = ExtractorConfig::default();
+        config.validation.enabled = false;
+        config.profile = ProfileSelection::Fixed(MonitorProfile {
+            name: "bottom_up".to_owned(),
+            rows: vec![
+                RowLayout::new(vec![FieldLayout::new(
+                    FieldRole::Pulse,
+                    DigitCount::between(2, 3),
+                )]),
+                RowLayout::new(vec![FieldLayout::new(
+                    FieldRole::Diastolic,
+                    DigitCount::between(2, 3),
+                )]),
+                RowLayout::new(vec![FieldLayout::new(
+                    FieldRole::Systolic,
+                    DigitCount::between(2, 3),
+                )]),
+            ],
+            indicators: Vec::new(),
+            date_time: None,
+            pressure_unit: PressureUnit::MmHg,
+            segment_sampling: None,
+        });
+
+        let result = get_reading_from_buffer_with_config(testfile, debug_session, config).unwrap();
+
+        assert_eq!(
+            result,
+            BloodPressureReading {
+                systolic: 77,
+                diastolic: 88,
+                pulse: 131,
+            }
+        );
+    }
+
+    #[test]
+    fn test_profile_detected_from_layout() {
+        let debug_session: DebuggerTrace<TempFolderDebugger> =
+            DebuggerTrace::temp_folder_session("test_profile_detected_from_layout");
+
+        let testfile = Vec::from(include_bytes!("./test_resources/example_top_down.jpg"));
+
+        let mut config = ExtractorConfig::default();
+        config.profile = ProfileSelection::Detect(vec![
+            MonitorProfile::pulse_beside_diastolic(),
+            MonitorProfile::date_time_above_stacked(),
+            MonitorProfile::stacked(),
+        ]);
+
+        let report = get_reading_report_from_buffer(testfile, debug_session, config).unwrap();
+
+        assert_eq!(report.profile_name, "stacked");
+        assert_eq!(
+            report.reading.reading(),
+            BloodPressureReading {
+                systolic: 131,
+                diastolic: 88,
+                pulse: 77,
+            }
+        );
+    }
+
+    #[test]
+    fn test_indicators_lit_from_profile_regions() {
+        let debug_session: DebuggerTrace<TempFolderDebugger> =
+            DebuggerTrace::temp_folder_session("test_indicators_lit_from_profile_regions");
+
+        let testfile = Vec::from(include_bytes!("./test_resources/example_top_down.jpg"));
+
+        // A region covering the whole screen always takes in the digits, and an empty one can never be lit
+        let mut profile = MonitorProfile::stacked();
+        let mut whole_screen = IndicatorLayout::new(
+            Indicator::IrregularHeartbeat,
+            RelativeRegion {
+                x: 0.,
+                y: 0.,
+                width: 1.,
+                height: 1.,
+            },
+        );
+        whole_screen.min_fill_ratio = 0.01;
+
+        profile.indicators = vec![
+            whole_screen,
+            IndicatorLayout::new(
+                Indicator::LowBattery,
+                RelativeRegion {
+                    x: 0.5,
+                    y: 0.5,
+                    width: 0.,
+                    height: 0.,
+                },
+            ),
+        ];
+
+        let mut config = ExtractorConfig::default();
+        config.profile = ProfileSelection::Fixed(profile);
+
+        let report = get_reading_report_from_buffer(testfile, debug_session, config).unwrap();
+
+        assert_eq!(report.indicators.irregular_heartbeat, Some(true));
+        assert_eq!(report.indicators.low_battery, Some(false));
+        assert_eq!(report.indicators.memory, None);
+        assert_eq!(report.indicators.lit(), vec![Indicator::IrregularHeartbeat]);
+    }
+
+    #[test]
+    fn test_seven_segment_reader_reads_rows() {
+        let debug_session: DebuggerTrace<TempFolderDebugger> =
+            DebuggerTrace::temp_folder_session("test_seven_segment_reader_reads_rows");
+
+        let testfile = Vector::from_slice(include_bytes!("./test_resources/example_top_down.jpg"));
+        let image = imgcodecs::imdecode(&testfile, ImreadModes::IMREAD_GRAYSCALE.into()).unwrap();
+
+        let extractor = BloodPressureReadingExtractor::default();
+        let resized_image = extractor.normalize_image(&image).unwrap();
+
+        let candidates = extractor
+            .screen_extractor
+            .get_ranked_lcd_candidates(&resized_image, &debug_session)
+            .unwrap();
+
+        let reader = SevenSegmentReader::default();
+
+        // The screen is not necessarily the best ranked candidate, so look for it among all of them
+        let found_screen = candidates.iter().any(|candidate| {
+            let Ok(lcd) =
+                extractor
+                    .screen_extractor
+                    .extract_lcd(&resized_image, candidate, &debug_session)
+            else {
+                return false;
+            };
+
+            let Ok(rows) = reader.read(&lcd.birdseye_view, &debug_session) else {
+                return false;
+            };
+
+            let values: Vec<Vec<Option<f64>>> = rows
+                .iter()
+                .map(|row| {
+                    row.numbers
+                        .iter()
+                        .map(|number| number.value().ok().flatten())
+                        .collect()
+                })
+                .collect();
+
+            values == vec![vec![Some(131.)], vec![Some(88.)], vec![Some(77.)]]
+        });
+
+        assert!(found_screen);
+    }
+
+    #[test]
+    fn test_font_with_both_digit_styles() {
+        let debug_session: DebuggerTrace<TempFolderDebugger> =
+            DebuggerTrace::temp_folder_session("test_font_with_both_digit_styles");
+
+        let testfile = Vec::from(include_bytes!("./test_resources/example_at_angle.jpg"));
+
+        let mut config = ExtractorConfig::default();
+        config.segments.font = SegmentFont::all_styles();
+
+        let result = get_reading_from_buffer_with_config(testfile, debug_session, config).unwrap();
+
+        assert_eq!(
+            result,
+            BloodPressureReading {
+                systolic: 133,
+                diastolic: 93,
+                pulse: 65,
+            }
+        );
+    }
+
+    #[test]
+    fn test_scanlines_evaluated_against_test_images() {
+        let test_images: [(&str, &[u8], BloodPressureReading); 3] = [
+            (
+                "example_at_angle",
+                include_bytes!("./test_resources/example_at_angle.jpg"),
+                BloodPressureReading {
+                    systolic: 133,
+                    diastolic: 93,
+                    pulse: 65,
+                },
+            ),
+            (
+                "example_top_down",
+                include_bytes!("./test_resources/example_top_down.jpg"),
+                BloodPressureReading {
+                    systolic: 131,
+                    diastolic: 88,
+                    pulse: 77,
+                },
+            ),
+            (
+                "contour_candidates",
+                include_bytes!("./test_resources/contour_candidates.jpeg"),
+                BloodPressureReading {
+                    systolic: 123,
+                    diastolic: 85,
+                    pulse: 68,
+                },
+            ),
+        ];
+
+        // Chosen by the profile, the way a monitor with a thin font would be set up
+        let profiles: Vec<MonitorProfile> = MonitorProfile::built_in_profiles()
+            .into_iter()
+            .map(|mut profile| {
+                profile.segment_sampling = Some(SegmentSampling::scanlines());
+                profile
+            })
+            .collect();
+
+        let mut config = ExtractorConfig::default();
+        config.profile = ProfileSelection::Detect(profiles);
+
+        for (name, testfile, expected_result) in test_images {
+            let debug_session: DebuggerTrace<TempFolderDebugger> =
+                DebuggerTrace::temp_folder_session(&format!("test_scanlines_{}", name));
+
+            let result = get_reading_from_buffer_with_config(
+                Vec::from(testfile),
+                debug_session,
+                config.clone(),
+            )
+            .unwrap();
+
+            assert_eq!(result, expected_result, "{}", name);
+        }
+    }
 }
@@ -1,32 +1,346 @@
-use std::sync::Arc;
+use std::{fmt, path::PathBuf, sync::Arc};
 
 use opencv::{
     Error,
-    core::{Point, Rect2i, Vector},
+    core::{Point, Point2f, Rect2i, Vector},
 };
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::debug::{BpmOcrDebugOutputter, NoDebug, TempFolderDebugger};
+use crate::{
+    debug::{BpmOcrDebugOutputter, DirectoryDebugger, NoDebug, TempFolderDebugger},
+    profile::{DigitCount, FieldRole, LayoutFingerprint},
+};
 
 #[derive(Clone, Debug)]
 pub enum ReadingIdentificationError {
     InternalError(&'static str),
     CouldNotIdentifyReadings,
     CouldNotIdentityLCDCandidate,
-    UnexpectedNumberOfRows,
-    CouldNotProcessSegments,
+    /// The digits on the LCD screen were not laid out in the expected number of rows
+    UnexpectedNumberOfRows {
+        expected: usize,
+        found: usize,
+    },
+    /// None of the monitor profiles to detect the profile from fit the layout of the digits on the LCD screen
+    NoMatchingMonitorProfile(LayoutFingerprint),
+    /// A field on the LCD screen did not have the number of digits its monitor profile expects
+    UnexpectedNumberOfDigits {
+        /// The row the field is on, counting from the top
+        row: usize,
+        role: FieldRole,
+        expected: DigitCount,
+        found: usize,
+    },
+    /// The segments lit up for a digit did not match any known digit
+    CouldNotProcessSegments {
+        field: ReadingField,
+        /// The position of the digit within the field, counting from the left
+        digit_index: usize,
+        /// Which segments were lit up, in the order top, top left, top right, centre, bottom left,
+        /// bottom right and bottom
+        segments: [bool; 7],
+        /// The fields that could be read despite the failure
+        partial_reading: PartialReading,
+    },
+    UnsupportedImageFormat,
+    InvalidMonitorProfile(&'static str),
+    InvalidSegmentFont(&'static str),
+    InvalidClassifier(&'static str),
+    /// One of the pipeline's tuning parameters is outside the range it can work with
+    InvalidConfig(&'static str),
+    /// Every field was read but the reading as a whole broke one of the plausibility rules
+    ImplausibleReading {
+        rule: PlausibilityRule,
+        reading: BloodPressureReading,
+    },
+    /// The monitor showed an error or status code, e.g. `Err`, `E1`, `Lo` or `---`, instead of a
+    /// reading. The measurement should be taken again.
+    DeviceReportedError {
+        code: String,
+    },
+}
+
+/// One of the numbers shown on a blood pressure monitor's LCD screen
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReadingField {
+    Systolic,
+    Diastolic,
+    Pulse,
+}
+
+impl fmt::Display for ReadingField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReadingField::Systolic => write!(f, "systolic"),
+            ReadingField::Diastolic => write!(f, "diastolic"),
+            ReadingField::Pulse => write!(f, "pulse"),
+        }
+    }
+}
+
+/// The fields of a reading that could be read, when the reading as a whole could not be
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct PartialReading {
+    pub systolic: Option<i32>,
+    pub diastolic: Option<i32>,
+    pub pulse: Option<i32>,
+}
+
+/// The rule a reading broke to be considered physiologically implausible
+#[derive(Clone, Debug, PartialEq)]
+pub enum PlausibilityRule {
+    SystolicOutOfRange,
+    DiastolicOutOfRange,
+    PulseOutOfRange,
+    DiastolicNotBelowSystolic,
+    PulsePressureTooNarrow,
+}
+
+impl fmt::Display for PlausibilityRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlausibilityRule::SystolicOutOfRange => write!(f, "systolic is out of range"),
+            PlausibilityRule::DiastolicOutOfRange => write!(f, "diastolic is out of range"),
+            PlausibilityRule::PulseOutOfRange => write!(f, "pulse is out of range"),
+            PlausibilityRule::DiastolicNotBelowSystolic => {
+                write!(f, "diastolic is not below systolic")
+            }
+            PlausibilityRule::PulsePressureTooNarrow => {
+                write!(f, "the gap between systolic and diastolic is too narrow")
+            }
+        }
+    }
+}
+
+impl ReadingIdentificationError {
+    /// A short name for the kind of error, suitable for file names and tallying errors by kind
+    pub fn name(&self) -> &'static str {
+        match self {
+            ReadingIdentificationError::InternalError(_) => "internal_error",
+            ReadingIdentificationError::CouldNotIdentifyReadings => "could_not_identify_readings",
+            ReadingIdentificationError::CouldNotIdentityLCDCandidate => {
+                "could_not_identify_lcd_candidate"
+            }
+            ReadingIdentificationError::UnexpectedNumberOfRows { .. } => {
+                "unexpected_number_of_rows"
+            }
+            ReadingIdentificationError::NoMatchingMonitorProfile(_) => {
+                "no_matching_monitor_profile"
+            }
+            ReadingIdentificationError::UnexpectedNumberOfDigits { .. } => {
+                "unexpected_number_of_digits"
+            }
+            ReadingIdentificationError::CouldNotProcessSegments { .. } => {
+                "could_not_process_segments"
+            }
+            ReadingIdentificationError::UnsupportedImageFormat => "unsupported_image_format",
+            ReadingIdentificationError::InvalidMonitorProfile(_) => "invalid_monitor_profile",
+            ReadingIdentificationError::InvalidSegmentFont(_) => "invalid_segment_font",
+            ReadingIdentificationError::InvalidClassifier(_) => "invalid_classifier",
+            ReadingIdentificationError::InvalidConfig(_) => "invalid_config",
+            ReadingIdentificationError::ImplausibleReading { .. } => "implausible_reading",
+            ReadingIdentificationError::DeviceReportedError { .. } => "device_reported_error",
+        }
+    }
+
+    /// How far through reading an LCD screen candidate the pipeline got before failing with this error
+    pub(crate) fn stage_reached(&self) -> u8 {
+        match self {
+            ReadingIdentificationError::UnexpectedNumberOfRows { .. } => 1,
+            ReadingIdentificationError::NoMatchingMonitorProfile(_) => 1,
+            ReadingIdentificationError::UnexpectedNumberOfDigits { .. } => 2,
+            ReadingIdentificationError::CouldNotProcessSegments { .. } => 3,
+            // Letters can be read from shapes on something other than the screen, so a code is
+            // trusted no more than a screen whose segments could not be read
+            ReadingIdentificationError::DeviceReportedError { .. } => 3,
+            ReadingIdentificationError::ImplausibleReading { .. } => 4,
+            _ => 0,
+        }
+    }
+
+    /// Whether the screen could not be read because of what was on it, in which case it may be
+    /// showing a device error or status code in place of the reading
+    pub(crate) fn may_be_device_code(&self) -> bool {
+        match self {
+            ReadingIdentificationError::UnexpectedNumberOfRows { .. }
+            | ReadingIdentificationError::NoMatchingMonitorProfile(_)
+            | ReadingIdentificationError::UnexpectedNumberOfDigits { .. }
+            | ReadingIdentificationError::CouldNotProcessSegments { .. } => true,
+            ReadingIdentificationError::InternalError(_)
+            | ReadingIdentificationError::CouldNotIdentifyReadings
+            | ReadingIdentificationError::CouldNotIdentityLCDCandidate
+            | ReadingIdentificationError::UnsupportedImageFormat
+            | ReadingIdentificationError::InvalidMonitorProfile(_)
+            | ReadingIdentificationError::InvalidSegmentFont(_)
+            | ReadingIdentificationError::InvalidClassifier(_)
+            | ReadingIdentificationError::InvalidConfig(_)
+            | ReadingIdentificationError::ImplausibleReading { .. }
+            | ReadingIdentificationError::DeviceReportedError { .. } => false,
+        }
+    }
+
+    /// The fields that were read before the error happened, if any were
+    pub fn partial_reading(&self) -> Option<PartialReading> {
+        match self {
+            ReadingIdentificationError::CouldNotProcessSegments {
+                partial_reading, ..
+            } => Some(partial_reading.clone()),
+            ReadingIdentificationError::ImplausibleReading { reading, .. } => {
+                Some(PartialReading {
+                    systolic: Some(reading.systolic),
+                    diastolic: Some(reading.diastolic),
+                    pulse: Some(reading.pulse),
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ReadingIdentificationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReadingIdentificationError::InternalError(message) => write!(f, "{}", message),
+            ReadingIdentificationError::CouldNotIdentifyReadings => {
+                write!(f, "could not identify the readings")
+            }
+            ReadingIdentificationError::CouldNotIdentityLCDCandidate => {
+                write!(f, "could not find the LCD screen")
+            }
+            ReadingIdentificationError::UnexpectedNumberOfRows { expected, found } => write!(
+                f,
+                "expected {} rows of digits on the LCD screen but found {}",
+                expected, found
+            ),
+            ReadingIdentificationError::NoMatchingMonitorProfile(fingerprint) => write!(
+                f,
+                "no monitor profile fits the {} rows of {} digits on the LCD screen",
+                fingerprint.rows.len(),
+                fingerprint.digit_count()
+            ),
+            ReadingIdentificationError::UnexpectedNumberOfDigits {
+                row,
+                role,
+                expected,
+                found,
+            } => write!(
+                f,
+                "expected between {} and {} digits in the {} field on row {} but found {}",
+                expected.min,
+                expected.max,
+                role,
+                row + 1,
+                found
+            ),
+            ReadingIdentificationError::CouldNotProcessSegments {
+                field,
+                digit_index,
+                segments,
+                partial_reading,
+            } => {
+                let segment_pattern: String = segments
+                    .iter()
+                    .map(|lit| if *lit { '1' } else { '0' })
+                    .collect();
+
+                write!(
+                    f,
+                    "digit {} of the {} reading has segments {} lit up, which is not a digit",
+                    digit_index + 1,
+                    field,
+                    segment_pattern
+                )?;
+
+                let read_fields: Vec<String> = [
+                    (ReadingField::Systolic, partial_reading.systolic),
+                    (ReadingField::Diastolic, partial_reading.diastolic),
+                    (ReadingField::Pulse, partial_reading.pulse),
+                ]
+                .into_iter()
+                .filter_map(|(field, value)| value.map(|value| format!("{} {}", field, value)))
+                .collect();
+
+                if read_fields.is_empty() {
+                    Ok(())
+                } else {
+                    write!(f, " (read {})", read_fields.join(", "))
+                }
+            }
+            ReadingIdentificationError::UnsupportedImageFormat => {
+                write!(f, "the image format is not supported")
+            }
+            ReadingIdentificationError::InvalidMonitorProfile(message) => {
+                write!(f, "invalid monitor profile: {}", message)
+            }
+            ReadingIdentificationError::InvalidSegmentFont(message) => {
+                write!(f, "invalid segment font: {}", message)
+            }
+            ReadingIdentificationError::InvalidClassifier(message) => {
+                write!(f, "invalid digit classifier: {}", message)
+            }
+            ReadingIdentificationError::InvalidConfig(message) => {
+                write!(f, "invalid config: {}", message)
+            }
+            ReadingIdentificationError::ImplausibleReading { rule, reading } => write!(
+                f,
+                "the reading {}/{} pulse {} is implausible: {}",
+                reading.systolic, reading.diastolic, reading.pulse, rule
+            ),
+            ReadingIdentificationError::DeviceReportedError { code } => write!(
+                f,
+                "the monitor showed the code {} instead of a reading, so the measurement should be taken again",
+                code
+            ),
+        }
+    }
 }
 
+impl std::error::Error for ReadingIdentificationError {}
+
 #[derive(Clone, Debug)]
 pub struct RejectedLcdScreenCandidate {
     pub contour: Vector<Point>,
 }
 
+/// How likely a quadrilateral is to be the LCD screen, along with the measures that went into deciding that
+#[derive(Clone, Debug)]
+pub struct LcdCandidateScore {
+    pub aspect_ratio: f64,
+    pub image_fraction: f64,
+    pub convex: bool,
+    pub contrast: f64,
+    pub score: f64,
+}
+
 #[derive(Clone, Debug)]
 pub struct LcdScreenCandidate {
     pub coordinates: Vector<Point>,
     pub area: f64,
     pub contour: Vector<Point>,
+    pub score: LcdCandidateScore,
+}
+
+/// Why a quadrilateral was decided not to be the LCD screen
+#[derive(Clone, Debug)]
+pub enum LcdCandidateRejection {
+    NotConvex,
+    ScoreTooLow(f64),
+    ReadingFailed(ReadingIdentificationError),
+    /// OpenCV failed while the candidate was being read, e.g. because its corners could not be
+    /// warped into a screen
+    ImageProcessingFailed(Error),
+}
+
+impl LcdCandidateRejection {
+    pub fn name(&self) -> &'static str {
+        match self {
+            LcdCandidateRejection::NotConvex => "not_convex",
+            LcdCandidateRejection::ScoreTooLow(_) => "score_too_low",
+            LcdCandidateRejection::ReadingFailed(error) => error.name(),
+            LcdCandidateRejection::ImageProcessingFailed(_) => "image_processing_failed",
+        }
+    }
 }
 
 pub enum LcdScreenCandidateResult {
@@ -40,6 +354,37 @@ pub enum ProcessingError {
     AppError(ReadingIdentificationError),
 }
 
+impl ProcessingError {
+    /// A short name for the kind of error, suitable for file names and tallying errors by kind
+    pub fn name(&self) -> &'static str {
+        match self {
+            ProcessingError::ImageDetectionLibraryError(_) => "image_detection_library_error",
+            ProcessingError::AppError(error) => error.name(),
+        }
+    }
+}
+
+impl fmt::Display for ProcessingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProcessingError::ImageDetectionLibraryError(error) => {
+                write!(f, "image processing failed: {}", error)
+            }
+            ProcessingError::AppError(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for ProcessingError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ProcessingError::ImageDetectionLibraryError(error) => Some(error),
+            // The application error is displayed as this error, so it is not a source of it as well
+            ProcessingError::AppError(_) => None,
+        }
+    }
+}
+
 impl From<Error> for ProcessingError {
     fn from(error: Error) -> Self {
         return Self::ImageDetectionLibraryError(error);
@@ -56,23 +401,302 @@ pub(crate) struct RectangleCoordinates {
 
 #[derive(Clone, Debug)]
 pub(crate) struct ReadingLocations {
+    /// The name of the monitor profile the fields were located with
+    pub profile_name: String,
     pub systolic_region: Vec<Rect2i>,
     pub diastolic_region: Vec<Rect2i>,
     pub pulse_region: Vec<Rect2i>,
 }
 
-#[derive(Clone, Debug, PartialEq)]
+/// Everything read from an LCD screen, along with where the digits were found on it
+#[derive(Clone, Debug)]
+pub(crate) struct ScreenReading {
+    pub reading: ReadingWithConfidence,
+    pub locations: ReadingLocations,
+    pub indicators: IndicatorFlags,
+    pub date_time: Option<DeviceDateTime>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct BloodPressureReading {
     pub systolic: i32,
     pub diastolic: i32,
     pub pulse: i32,
 }
 
+/// A recognised digit along with how confident we are that it was read correctly. The confidence
+/// ranges from 0.0 (a segment was right on the edge of being considered lit up) to 1.0.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DigitConfidence {
+    pub digit: i32,
+    pub confidence: f32,
+    /// The next most likely digit given the lit up segments. There is none for a 1 recognised by
+    /// its narrow shape alone.
+    pub runner_up: Option<i32>,
+    /// How much more likely the digit is than the runner up, from 0.0 (a coin toss) to 1.0
+    pub margin: f32,
+}
+
+/// A number read from one of the rows of the LCD screen along with the confidence in each digit.
+/// The field's confidence is that of its least confident digit.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FieldConfidence {
+    /// The digits of the number, ignoring any decimal point
+    pub value: i32,
+    /// How many of the digits come after the decimal point
+    pub decimal_places: u32,
+    pub confidence: f32,
+    pub digits: Vec<DigitConfidence>,
+}
+
+impl FieldConfidence {
+    /// The number as it was shown on the screen, decimal point included
+    pub fn displayed_value(&self) -> f64 {
+        self.value as f64 / 10_f64.powi(self.decimal_places as i32)
+    }
+}
+
+/// The number of millimetres of mercury in a kilopascal
+const MMHG_PER_KPA: f64 = 7.50062;
+
+/// A unit blood pressure can be shown in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PressureUnit {
+    #[default]
+    MmHg,
+    KPa,
+}
+
+impl PressureUnit {
+    pub fn to_mmhg(&self, pressure: f64) -> f64 {
+        match self {
+            PressureUnit::MmHg => pressure,
+            PressureUnit::KPa => pressure * MMHG_PER_KPA,
+        }
+    }
+}
+
+impl fmt::Display for PressureUnit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PressureUnit::MmHg => write!(f, "mmHg"),
+            PressureUnit::KPa => write!(f, "kPa"),
+        }
+    }
+}
+
+/// A reading as the monitor showed it, with the blood pressure in whichever unit the monitor uses
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DisplayedReading {
+    pub systolic: f64,
+    pub diastolic: f64,
+    pub pulse: i32,
+    pub unit: PressureUnit,
+}
+
+impl DisplayedReading {
+    /// The reading with the blood pressure converted to the nearest whole mmHg
+    pub fn to_mmhg(&self) -> BloodPressureReading {
+        BloodPressureReading {
+            systolic: self.unit.to_mmhg(self.systolic).round() as i32,
+            diastolic: self.unit.to_mmhg(self.diastolic).round() as i32,
+            pulse: self.pulse,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReadingWithConfidence {
+    pub systolic: FieldConfidence,
+    pub diastolic: FieldConfidence,
+    pub pulse: FieldConfidence,
+    /// The unit the monitor showed the blood pressure in
+    pub unit: PressureUnit,
+}
+
+impl ReadingWithConfidence {
+    /// The reading with the blood pressure in mmHg, whichever unit the monitor showed it in
+    pub fn reading(&self) -> BloodPressureReading {
+        self.displayed().to_mmhg()
+    }
+
+    /// The reading as the monitor showed it
+    pub fn displayed(&self) -> DisplayedReading {
+        DisplayedReading {
+            systolic: self.systolic.displayed_value(),
+            diastolic: self.diastolic.displayed_value(),
+            pulse: self.pulse.value,
+            unit: self.unit,
+        }
+    }
+
+    /// The confidence in the reading as a whole, which is that of its least confident field
+    pub fn confidence(&self) -> f32 {
+        self.systolic
+            .confidence
+            .min(self.diastolic.confidence)
+            .min(self.pulse.confidence)
+    }
+}
+
+/// An icon an LCD screen may show alongside the digits of a reading
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Indicator {
+    IrregularHeartbeat,
+    /// The reading shown is from memory, or is an average of readings in memory
+    Memory,
+    User1,
+    User2,
+    LowBattery,
+    /// The cuff was not wrapped correctly while the reading was taken
+    CuffWrapCheck,
+    /// The blood pressure is shown in kilopascals
+    KpaUnit,
+    /// The blood pressure is shown in millimetres of mercury
+    MmHgUnit,
+}
+
+/// Which of the indicator icons were lit on the LCD screen. Icons the monitor profile does not
+/// describe are not checked, so are `None` rather than unlit.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct IndicatorFlags {
+    pub irregular_heartbeat: Option<bool>,
+    pub memory: Option<bool>,
+    pub user_1: Option<bool>,
+    pub user_2: Option<bool>,
+    pub low_battery: Option<bool>,
+    pub cuff_wrap_check: Option<bool>,
+    pub kpa_unit: Option<bool>,
+    pub mmhg_unit: Option<bool>,
+}
+
+impl fmt::Display for Indicator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Indicator::IrregularHeartbeat => write!(f, "irregular heartbeat"),
+            Indicator::Memory => write!(f, "memory"),
+            Indicator::User1 => write!(f, "user 1"),
+            Indicator::User2 => write!(f, "user 2"),
+            Indicator::LowBattery => write!(f, "low battery"),
+            Indicator::CuffWrapCheck => write!(f, "cuff wrap check"),
+            Indicator::KpaUnit => write!(f, "kPa"),
+            Indicator::MmHgUnit => write!(f, "mmHg"),
+        }
+    }
+}
+
+impl IndicatorFlags {
+    /// Every indicator that was lit
+    pub fn lit(&self) -> Vec<Indicator> {
+        [
+            Indicator::IrregularHeartbeat,
+            Indicator::Memory,
+            Indicator::User1,
+            Indicator::User2,
+            Indicator::LowBattery,
+            Indicator::CuffWrapCheck,
+            Indicator::KpaUnit,
+            Indicator::MmHgUnit,
+        ]
+        .into_iter()
+        .filter(|indicator| self.is_lit(*indicator))
+        .collect()
+    }
+
+    /// Whether the indicator was lit, or nothing if the monitor profile does not describe where it is
+    pub fn state(&self, indicator: Indicator) -> Option<bool> {
+        match indicator {
+            Indicator::IrregularHeartbeat => self.irregular_heartbeat,
+            Indicator::Memory => self.memory,
+            Indicator::User1 => self.user_1,
+            Indicator::User2 => self.user_2,
+            Indicator::LowBattery => self.low_battery,
+            Indicator::CuffWrapCheck => self.cuff_wrap_check,
+            Indicator::KpaUnit => self.kpa_unit,
+            Indicator::MmHgUnit => self.mmhg_unit,
+        }
+    }
+
+    /// Whether the indicator was checked and found to be lit
+    pub fn is_lit(&self, indicator: Indicator) -> bool {
+        self.state(indicator) == Some(true)
+    }
+
+    pub fn set_lit(&mut self, indicator: Indicator, lit: bool) {
+        let flag = match indicator {
+            Indicator::IrregularHeartbeat => &mut self.irregular_heartbeat,
+            Indicator::Memory => &mut self.memory,
+            Indicator::User1 => &mut self.user_1,
+            Indicator::User2 => &mut self.user_2,
+            Indicator::LowBattery => &mut self.low_battery,
+            Indicator::CuffWrapCheck => &mut self.cuff_wrap_check,
+            Indicator::KpaUnit => &mut self.kpa_unit,
+            Indicator::MmHgUnit => &mut self.mmhg_unit,
+        };
+
+        *flag = Some(lit);
+    }
+}
+
+/// The date and time a monitor shows alongside a reading. Monitors do not show the year, and some
+/// only show the time.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DeviceDateTime {
+    pub month: Option<u32>,
+    pub day: Option<u32>,
+    pub hour: u32,
+    pub minute: u32,
+}
+
+/// Four corners of a shape in the original photo, e.g. the LCD screen or a digit seen at an angle
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quadrilateral {
+    pub top_left: Point2f,
+    pub top_right: Point2f,
+    pub bottom_right: Point2f,
+    pub bottom_left: Point2f,
+}
+
+/// A reading along with where everything that contributed to it was found in the original photo
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReadingReport {
+    pub reading: ReadingWithConfidence,
+    /// The name of the monitor profile the reading was taken with
+    pub profile_name: String,
+    /// The indicator icons that were lit alongside the reading
+    pub indicators: IndicatorFlags,
+    /// The date and time shown alongside the reading, if the monitor profile says where to find it
+    /// and it could be read
+    pub date_time: Option<DeviceDateTime>,
+    /// The corners of the LCD screen in the original photo
+    pub lcd_corners: Quadrilateral,
+    /// The homography mapping points in the original photo onto the perspective corrected LCD screen
+    pub perspective_transform: [[f64; 3]; 3],
+    /// The corners of each systolic digit in the original photo, from left to right
+    pub systolic_digits: Vec<Quadrilateral>,
+    /// The corners of each diastolic digit in the original photo, from left to right
+    pub diastolic_digits: Vec<Quadrilateral>,
+    /// The corners of each pulse digit in the original photo, from left to right
+    pub pulse_digits: Vec<Quadrilateral>,
+}
+
 pub struct DebuggerTrace<T: BpmOcrDebugOutputter> {
     pub unique_trace_name: String,
     pub debugger: Arc<T>,
 }
 
+impl<T: BpmOcrDebugOutputter> DebuggerTrace<T> {
+    /// Creates a trace that outputs debug images with an existing debugger, e.g. one shared between many images
+    pub fn new(unique_trace_name: &str, debugger: Arc<T>) -> Self {
+        DebuggerTrace {
+            unique_trace_name: unique_trace_name.to_owned(),
+            debugger,
+        }
+    }
+}
+
 impl DebuggerTrace<NoDebug> {
     pub fn no_debug_session() -> Self {
         DebuggerTrace {
@@ -98,3 +722,135 @@ impl DebuggerTrace<TempFolderDebugger> {
         }
     }
 }
+
+impl DebuggerTrace<DirectoryDebugger> {
+    pub fn directory_session(directory: PathBuf, unique_session_name: &str) -> Self {
+        DebuggerTrace {
+            debugger: Arc::new(DirectoryDebugger::in_directory(directory)),
+            unique_trace_name: unique_session_name.to_owned(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_messages() {
+        let rows = ProcessingError::AppError(ReadingIdentificationError::UnexpectedNumberOfRows {
+            expected: 3,
+            found: 2,
+        });
+
+        assert_eq!(
+            rows.to_string(),
+            "expected 3 rows of digits on the LCD screen but found 2"
+        );
+        assert_eq!(rows.name(), "unexpected_number_of_rows");
+        assert!(std::error::Error::source(&rows).is_none());
+
+        let segments = ReadingIdentificationError::CouldNotProcessSegments {
+            field: ReadingField::Pulse,
+            digit_index: 1,
+            segments: [true, false, true, false, false, true, true],
+            partial_reading: PartialReading {
+                systolic: Some(120),
+                diastolic: Some(80),
+                pulse: None,
+            },
+        };
+
+        assert_eq!(
+            segments.to_string(),
+            "digit 2 of the pulse reading has segments 1010011 lit up, which is not a digit (read systolic 120, diastolic 80)"
+        );
+    }
+
+    #[test]
+    fn test_device_code_only_looked_for_when_screen_unreadable() {
+        let rows = ReadingIdentificationError::UnexpectedNumberOfRows {
+            expected: 3,
+            found: 1,
+        };
+        let implausible = ReadingIdentificationError::ImplausibleReading {
+            rule: PlausibilityRule::PulseOutOfRange,
+            reading: BloodPressureReading {
+                systolic: 120,
+                diastolic: 80,
+                pulse: 300,
+            },
+        };
+
+        assert!(rows.may_be_device_code());
+        assert!(!implausible.may_be_device_code());
+        assert!(!ReadingIdentificationError::UnsupportedImageFormat.may_be_device_code());
+    }
+
+    #[test]
+    fn test_partial_reading_from_errors() {
+        let segments = ReadingIdentificationError::CouldNotProcessSegments {
+            field: ReadingField::Diastolic,
+            digit_index: 0,
+            segments: [false; 7],
+            partial_reading: PartialReading {
+                systolic: Some(131),
+                diastolic: None,
+                pulse: None,
+            },
+        };
+
+        assert_eq!(
+            segments.partial_reading(),
+            Some(PartialReading {
+                systolic: Some(131),
+                diastolic: None,
+                pulse: None,
+            })
+        );
+
+        let implausible = ReadingIdentificationError::ImplausibleReading {
+            rule: PlausibilityRule::PulseOutOfRange,
+            reading: BloodPressureReading {
+                systolic: 131,
+                diastolic: 88,
+                pulse: 300,
+            },
+        };
+
+        assert_eq!(
+            implausible.partial_reading(),
+            Some(PartialReading {
+                systolic: Some(131),
+                diastolic: Some(88),
+                pulse: Some(300),
+            })
+        );
+
+        let rows = ReadingIdentificationError::UnexpectedNumberOfRows {
+            expected: 3,
+            found: 4,
+        };
+
+        assert_eq!(rows.partial_reading(), None);
+    }
+
+    #[test]
+    fn test_kpa_reading_converted_to_mmhg() {
+        let displayed = DisplayedReading {
+            systolic: 17.7,
+            diastolic: 11.2,
+            pulse: 77,
+            unit: PressureUnit::KPa,
+        };
+
+        assert_eq!(
+            displayed.to_mmhg(),
+            BloodPressureReading {
+                systolic: 133,
+                diastolic: 84,
+                pulse: 77,
+            }
+        );
+    }
+}
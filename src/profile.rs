@@ -0,0 +1,622 @@
+use std::fmt;
+
+use opencv::core::Rect2i;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::SegmentSampling,
+    models::{Indicator, PressureUnit, ReadingField},
+};
+
+/// What a group of digits on the LCD screen shows
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldRole {
+    Systolic,
+    Diastolic,
+    Pulse,
+    /// Digits that are expected to be on the screen but are not part of the reading, e.g. a memory number
+    Ignored,
+}
+
+impl FieldRole {
+    pub fn reading_field(&self) -> Option<ReadingField> {
+        match self {
+            FieldRole::Systolic => Some(ReadingField::Systolic),
+            FieldRole::Diastolic => Some(ReadingField::Diastolic),
+            FieldRole::Pulse => Some(ReadingField::Pulse),
+            FieldRole::Ignored => None,
+        }
+    }
+}
+
+impl fmt::Display for FieldRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.reading_field() {
+            Some(reading_field) => write!(f, "{}", reading_field),
+            None => write!(f, "ignored"),
+        }
+    }
+}
+
+/// An inclusive range of how many digits a field is expected to have
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DigitCount {
+    pub min: usize,
+    pub max: usize,
+}
+
+impl DigitCount {
+    pub fn between(min: usize, max: usize) -> Self {
+        DigitCount { min, max }
+    }
+
+    pub fn contains(&self, count: usize) -> bool {
+        self.min <= count && count <= self.max
+    }
+}
+
+/// A group of digits within a row of the LCD screen
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FieldLayout {
+    pub role: FieldRole,
+    pub digits: DigitCount,
+}
+
+impl FieldLayout {
+    pub fn new(role: FieldRole, digits: DigitCount) -> Self {
+        FieldLayout { role, digits }
+    }
+
+    /// A blood pressure or pulse field, which has either two or three digits
+    fn reading(role: FieldRole) -> Self {
+        FieldLayout::new(role, DigitCount::between(2, 3))
+    }
+}
+
+/// Which side of the screen a row of digits lines up with, relative to the other rows
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RowAlignment {
+    #[default]
+    Any,
+    Left,
+    Centre,
+    Right,
+}
+
+/// A row of digits on the LCD screen. A row with more than one field is split into its fields at
+/// the widest gaps between its digits.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RowLayout {
+    /// The fields in the row, from left to right
+    pub fields: Vec<FieldLayout>,
+    /// The height of the row's digits relative to those of the tallest row, if the monitor is
+    /// known to show the row at a particular size. Only used to detect the profile.
+    #[serde(default)]
+    pub relative_height: Option<f32>,
+    /// How far down the screen the row's digits start, as a fraction of the height spanned by all
+    /// of the digits, if the monitor is known to show the row in a particular place. Only used to
+    /// detect the profile.
+    #[serde(default)]
+    pub relative_top: Option<f32>,
+    /// Only used to detect the profile
+    #[serde(default)]
+    pub alignment: RowAlignment,
+}
+
+impl RowLayout {
+    pub fn new(fields: Vec<FieldLayout>) -> Self {
+        RowLayout {
+            fields,
+            relative_height: None,
+            relative_top: None,
+            alignment: RowAlignment::Any,
+        }
+    }
+
+    /// How many digits the row's fields add up to
+    pub fn digit_count(&self) -> DigitCount {
+        DigitCount::between(
+            self.fields.iter().map(|field| field.digits.min).sum(),
+            self.fields.iter().map(|field| field.digits.max).sum(),
+        )
+    }
+
+    /// How well a row of digits on the screen fits this row, from 0.0 to 1.0, or nothing if it
+    /// has a number of digits that the row's fields cannot add up to
+    fn match_score(&self, row: &RowFingerprint) -> Option<f32> {
+        if !self.digit_count().contains(row.digit_count) {
+            return None;
+        }
+
+        let height_score = self
+            .relative_height
+            .map(|expected| 1.0 - (expected - row.relative_height).abs().min(1.0))
+            .unwrap_or(1.0);
+
+        let position_score = self
+            .relative_top
+            .map(|expected| 1.0 - (expected - row.relative_top).abs().min(1.0))
+            .unwrap_or(1.0);
+
+        let alignment_score = match self.alignment {
+            RowAlignment::Any => 1.0,
+            RowAlignment::Left => 1.0 - row.left_offset,
+            RowAlignment::Right => 1.0 - row.right_offset,
+            RowAlignment::Centre => 1.0 - (row.left_offset - row.right_offset).abs(),
+        };
+
+        Some(height_score * position_score * alignment_score)
+    }
+}
+
+/// A rectangle on the LCD screen, given in fractions of the screen's width and height from its top left corner
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RelativeRegion {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl RelativeRegion {
+    /// The region in pixels on a screen of the given size, clipped to the screen
+    pub(crate) fn to_rect(&self, screen_width: i32, screen_height: i32) -> Rect2i {
+        let left = ((self.x * screen_width as f32) as i32).clamp(0, screen_width);
+        let top = ((self.y * screen_height as f32) as i32).clamp(0, screen_height);
+        let right =
+            (((self.x + self.width) * screen_width as f32) as i32).clamp(left, screen_width);
+        let bottom =
+            (((self.y + self.height) * screen_height as f32) as i32).clamp(top, screen_height);
+
+        Rect2i::new(left, top, right - left, bottom - top)
+    }
+}
+
+fn default_min_fill_ratio() -> f32 {
+    0.15
+}
+
+/// Where an indicator icon appears on the LCD screen
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct IndicatorLayout {
+    pub indicator: Indicator,
+    pub region: RelativeRegion,
+    /// The fraction of the region that must be dark for the icon to count as lit
+    #[serde(default = "default_min_fill_ratio")]
+    pub min_fill_ratio: f32,
+}
+
+impl IndicatorLayout {
+    pub fn new(indicator: Indicator, region: RelativeRegion) -> Self {
+        IndicatorLayout {
+            indicator,
+            region,
+            min_fill_ratio: default_min_fill_ratio(),
+        }
+    }
+}
+
+/// The order a monitor shows the month and day of the date in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DateOrder {
+    #[default]
+    MonthDay,
+    DayMonth,
+}
+
+fn default_min_date_time_digit_height_ratio() -> f32 {
+    0.5
+}
+
+/// Where the date and time are shown on the LCD screen. The time is expected to have a colon
+/// between the hour and minute, and the date to come before the time with its month and day
+/// split by a separator or a gap.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DateTimeLayout {
+    pub region: RelativeRegion,
+    #[serde(default)]
+    pub date_order: DateOrder,
+    /// Shapes at least this fraction of the region's height are digits. Anything smaller may be a
+    /// dot of the colon or a date separator.
+    #[serde(default = "default_min_date_time_digit_height_ratio")]
+    pub min_digit_height_ratio: f32,
+}
+
+impl DateTimeLayout {
+    pub fn new(region: RelativeRegion) -> Self {
+        DateTimeLayout {
+            region,
+            date_order: DateOrder::MonthDay,
+            min_digit_height_ratio: default_min_date_time_digit_height_ratio(),
+        }
+    }
+}
+
+/// Describes where a model of blood pressure monitor shows each field of a reading on its LCD
+/// screen. Only digits tall enough to pass the digit location settings count towards the layout.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MonitorProfile {
+    pub name: String,
+    /// The rows of digits on the screen, from top to bottom
+    pub rows: Vec<RowLayout>,
+    /// The indicator icons the screen can show. The built in profiles describe none, as where the
+    /// icons appear differs between every model.
+    #[serde(default)]
+    pub indicators: Vec<IndicatorLayout>,
+    /// Where the screen shows the date and time the reading was taken, if it does. Digits within
+    /// the region are left out of the rows.
+    #[serde(default)]
+    pub date_time: Option<DateTimeLayout>,
+    /// The unit the monitor shows blood pressure in. A lit kPa or mmHg indicator takes precedence,
+    /// for monitors that can show either.
+    #[serde(default)]
+    pub pressure_unit: PressureUnit,
+    /// How the digits of this model's screen are sampled, if it differs from the segment settings,
+    /// e.g. scanlines for a screen with a thin font, or zones sized to fit its strokes
+    #[serde(default)]
+    pub segment_sampling: Option<SegmentSampling>,
+}
+
+impl Default for MonitorProfile {
+    fn default() -> Self {
+        MonitorProfile::stacked()
+    }
+}
+
+impl MonitorProfile {
+    /// Systolic, diastolic and pulse in three rows, one above the other
+    pub fn stacked() -> Self {
+        MonitorProfile {
+            name: "stacked".to_owned(),
+            rows: vec![
+                RowLayout::new(vec![FieldLayout::reading(FieldRole::Systolic)]),
+                RowLayout::new(vec![FieldLayout::reading(FieldRole::Diastolic)]),
+                RowLayout::new(vec![FieldLayout::reading(FieldRole::Pulse)]),
+            ],
+            indicators: Vec::new(),
+            date_time: None,
+            pressure_unit: PressureUnit::MmHg,
+            segment_sampling: None,
+        }
+    }
+
+    /// Systolic on the top row, with diastolic and then pulse beside each other on the row below
+    pub fn pulse_beside_diastolic() -> Self {
+        MonitorProfile {
+            name: "pulse_beside_diastolic".to_owned(),
+            rows: vec![
+                RowLayout::new(vec![FieldLayout::reading(FieldRole::Systolic)]),
+                RowLayout::new(vec![
+                    FieldLayout::reading(FieldRole::Diastolic),
+                    FieldLayout::reading(FieldRole::Pulse),
+                ]),
+            ],
+            indicators: Vec::new(),
+            date_time: None,
+            pressure_unit: PressureUnit::MmHg,
+            segment_sampling: None,
+        }
+    }
+
+    /// Systolic and then diastolic beside each other on the top row, with pulse on the row below
+    pub fn pressures_side_by_side() -> Self {
+        MonitorProfile {
+            name: "pressures_side_by_side".to_owned(),
+            rows: vec![
+                RowLayout::new(vec![
+                    FieldLayout::reading(FieldRole::Systolic),
+                    FieldLayout::reading(FieldRole::Diastolic),
+                ]),
+                RowLayout::new(vec![FieldLayout::reading(FieldRole::Pulse)]),
+            ],
+            indicators: Vec::new(),
+            date_time: None,
+            pressure_unit: PressureUnit::MmHg,
+            segment_sampling: None,
+        }
+    }
+
+    /// The date and time of the reading in small digits along the top of the screen, above
+    /// systolic, diastolic and pulse in three rows. Detection only picks it over `stacked` when
+    /// there are digits along the top of the screen.
+    pub fn date_time_above_stacked() -> Self {
+        let mut profile = MonitorProfile::stacked();
+        profile.name = "date_time_above_stacked".to_owned();
+        profile.date_time = Some(DateTimeLayout::new(RelativeRegion {
+            x: 0.,
+            y: 0.,
+            width: 1.,
+            height: 0.22,
+        }));
+
+        profile
+    }
+
+    /// Systolic, diastolic and pulse in three rows, one above the other, with the blood pressure
+    /// in kPa to one decimal place. It reads the same rows as `stacked`, so detection only picks it
+    /// over `stacked` if it is given first.
+    pub fn stacked_kpa() -> Self {
+        let mut profile = MonitorProfile::stacked();
+        profile.name = "stacked_kpa".to_owned();
+        profile.pressure_unit = PressureUnit::KPa;
+
+        profile
+    }
+
+    /// Every profile that ships with the library
+    pub fn built_in_profiles() -> Vec<MonitorProfile> {
+        vec![
+            MonitorProfile::stacked(),
+            MonitorProfile::pulse_beside_diastolic(),
+            MonitorProfile::pressures_side_by_side(),
+            MonitorProfile::date_time_above_stacked(),
+            MonitorProfile::stacked_kpa(),
+        ]
+    }
+
+    /// Looks up a profile that ships with the library by its name
+    pub fn built_in(name: &str) -> Option<MonitorProfile> {
+        MonitorProfile::built_in_profiles()
+            .into_iter()
+            .find(|profile| profile.name == name)
+    }
+
+    /// How well the layout of the digits on a screen fits this profile, from 0.0 to 1.0, or nothing
+    /// if the screen has a different number of rows or a row's digits cannot be split into its fields
+    pub fn match_score(&self, fingerprint: &LayoutFingerprint) -> Option<f32> {
+        if self.rows.len() != fingerprint.rows.len() {
+            return None;
+        }
+
+        self.rows
+            .iter()
+            .zip(&fingerprint.rows)
+            .map(|(row_layout, row)| row_layout.match_score(row))
+            .product()
+    }
+
+    /// Checks that the profile has one field for each of systolic, diastolic and pulse, and that
+    /// every row and field could actually be found on a screen
+    pub fn validate(&self) -> Result<(), &'static str> {
+        let mut fields = self.rows.iter().flat_map(|row| row.fields.iter());
+
+        for reading_field in [
+            ReadingField::Systolic,
+            ReadingField::Diastolic,
+            ReadingField::Pulse,
+        ] {
+            let occurrences = fields
+                .clone()
+                .filter(|field| field.role.reading_field() == Some(reading_field))
+                .count();
+
+            if occurrences != 1 {
+                return Err(
+                    "The profile must have exactly one systolic, diastolic and pulse field",
+                );
+            }
+        }
+
+        if self.rows.iter().any(|row| row.fields.is_empty()) {
+            return Err("Every row of the profile must have at least one field");
+        }
+
+        if fields.any(|field| field.digits.min == 0 || field.digits.min > field.digits.max) {
+            return Err("Every field of the profile must expect at least one digit");
+        }
+
+        match &self.segment_sampling {
+            Some(sampling) => sampling.validate(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Either a single monitor profile to read every screen with, or a set of profiles to choose from
+/// by the layout of the digits on each screen
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProfileSelection {
+    Fixed(MonitorProfile),
+    /// The profile that best matches the layout is used. Profiles that match equally well are
+    /// preferred in the order they are given.
+    Detect(Vec<MonitorProfile>),
+}
+
+impl Default for ProfileSelection {
+    fn default() -> Self {
+        ProfileSelection::Fixed(MonitorProfile::stacked())
+    }
+}
+
+impl ProfileSelection {
+    /// Detects the profile from every profile that ships with the library
+    pub fn detect_built_in() -> Self {
+        ProfileSelection::Detect(MonitorProfile::built_in_profiles())
+    }
+
+    /// Every profile the screen may be read with
+    pub fn profiles(&self) -> &[MonitorProfile] {
+        match self {
+            ProfileSelection::Fixed(profile) => std::slice::from_ref(profile),
+            ProfileSelection::Detect(profiles) => profiles,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), &'static str> {
+        match self {
+            ProfileSelection::Fixed(profile) => profile.validate(),
+            ProfileSelection::Detect(profiles) if profiles.is_empty() => {
+                Err("At least one profile must be given to detect the profile from")
+            }
+            ProfileSelection::Detect(profiles) => {
+                profiles.iter().map(|profile| profile.validate()).collect()
+            }
+        }
+    }
+
+    /// Picks the profile to read a screen with the given layout with, if any fits it
+    pub fn select(&self, fingerprint: &LayoutFingerprint) -> Option<&MonitorProfile> {
+        self.select_with(|profile| profile.match_score(fingerprint))
+    }
+
+    /// Picks the profile that scores best, if any fits the screen, for when each profile needs to
+    /// see the screen's layout differently, e.g. without the digits in its date and time region
+    pub fn select_with(
+        &self,
+        match_score: impl Fn(&MonitorProfile) -> Option<f32>,
+    ) -> Option<&MonitorProfile> {
+        match self {
+            ProfileSelection::Fixed(profile) => Some(profile),
+            ProfileSelection::Detect(profiles) => profiles
+                .iter()
+                .filter_map(|profile| match_score(profile).map(|score| (profile, score)))
+                .fold(
+                    None,
+                    |best: Option<(&MonitorProfile, f32)>, candidate| match best {
+                        Some(best) if best.1 >= candidate.1 => Some(best),
+                        _ => Some(candidate),
+                    },
+                )
+                .map(|(profile, _)| profile),
+        }
+    }
+}
+
+/// The shape of one row of digits found on an LCD screen
+#[derive(Clone, Debug, PartialEq)]
+pub struct RowFingerprint {
+    pub digit_count: usize,
+    /// The mean height of the row's digits relative to that of the tallest row
+    pub relative_height: f32,
+    /// How far the row starts from the highest digit on the screen, as a fraction of the height
+    /// spanned by all of the digits
+    pub relative_top: f32,
+    /// How far the row starts from the leftmost digit on the screen, as a fraction of the width
+    /// spanned by all of the digits
+    pub left_offset: f32,
+    /// How far the row ends from the rightmost digit on the screen, as a fraction of the width
+    /// spanned by all of the digits
+    pub right_offset: f32,
+}
+
+/// The shape of the digits found on an LCD screen, used to pick the monitor profile that best
+/// describes the screen
+#[derive(Clone, Debug, PartialEq)]
+pub struct LayoutFingerprint {
+    /// The rows of digits, from top to bottom
+    pub rows: Vec<RowFingerprint>,
+}
+
+impl LayoutFingerprint {
+    /// Takes the fingerprint of digits already grouped into rows from top to bottom, with each row's
+    /// digits sorted from left to right
+    pub(crate) fn from_rows(rows: &[Vec<Rect2i>]) -> Self {
+        let digits = rows.iter().flatten();
+        let left = digits.clone().map(|digit| digit.x).min().unwrap_or(0);
+        let right = digits
+            .clone()
+            .map(|digit| digit.x + digit.width)
+            .max()
+            .unwrap_or(0);
+        let width = ((right - left) as f32).max(1.);
+
+        let top = digits.clone().map(|digit| digit.y).min().unwrap_or(0);
+        let bottom = digits
+            .map(|digit| digit.y + digit.height)
+            .max()
+            .unwrap_or(0);
+        let height = ((bottom - top) as f32).max(1.);
+
+        let mean_heights: Vec<f32> = rows
+            .iter()
+            .map(|row| {
+                row.iter().map(|digit| digit.height as f32).sum::<f32>() / row.len().max(1) as f32
+            })
+            .collect();
+        let tallest = mean_heights.iter().cloned().fold(1., f32::max);
+
+        let rows = rows
+            .iter()
+            .zip(mean_heights)
+            .map(|(row, mean_height)| {
+                let row_top = row.iter().map(|digit| digit.y).min().unwrap_or(top);
+                let row_left = row.iter().map(|digit| digit.x).min().unwrap_or(left);
+                let row_right = row
+                    .iter()
+                    .map(|digit| digit.x + digit.width)
+                    .max()
+                    .unwrap_or(right);
+
+                RowFingerprint {
+                    digit_count: row.len(),
+                    relative_height: mean_height / tallest,
+                    relative_top: (row_top - top) as f32 / height,
+                    left_offset: (row_left - left) as f32 / width,
+                    right_offset: (right - row_right) as f32 / width,
+                }
+            })
+            .collect();
+
+        LayoutFingerprint { rows }
+    }
+
+    pub fn digit_count(&self) -> usize {
+        self.rows.iter().map(|row| row.digit_count).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile_detected_from_row_positions() {
+        let row = |y: i32, digit_count: i32| -> Vec<Rect2i> {
+            (0..digit_count)
+                .map(|index| Rect2i::new(index * 30, y, 20, 40))
+                .collect()
+        };
+
+        // The second row starts 100 pixels into the 140 pixels spanned by the digits
+        let fingerprint = LayoutFingerprint::from_rows(&[row(0, 3), row(100, 2)]);
+        assert_eq!(fingerprint.rows[0].relative_top, 0.0);
+        assert!((fingerprint.rows[1].relative_top - 100.0 / 140.0).abs() < 1e-6);
+
+        let profile = |name: &str, second_row_top: f32| {
+            let mut second_row = RowLayout::new(vec![FieldLayout::new(
+                FieldRole::Diastolic,
+                DigitCount::between(2, 3),
+            )]);
+            second_row.relative_top = Some(second_row_top);
+
+            MonitorProfile {
+                name: name.to_owned(),
+                rows: vec![
+                    RowLayout::new(vec![FieldLayout::new(
+                        FieldRole::Systolic,
+                        DigitCount::between(2, 3),
+                    )]),
+                    second_row,
+                ],
+                indicators: Vec::new(),
+                date_time: None,
+                pressure_unit: PressureUnit::MmHg,
+                segment_sampling: None,
+            }
+        };
+
+        let selection =
+            ProfileSelection::Detect(vec![profile("middle", 0.3), profile("bottom", 0.7)]);
+
+        assert_eq!(
+            selection
+                .select(&fingerprint)
+                .map(|profile| profile.name.as_str()),
+            Some("bottom")
+        );
+    }
+}
@@ -1,6 +1,13 @@
-use opencv::core::{Point, Vector};
+use std::cmp::max;
 
-use crate::models::{self};
+use opencv::{
+    Error,
+    core::{Mat, MatTraitConst, Point, Point2f, Rect2i, Vector},
+};
+
+use crate::models::{self, Quadrilateral};
+
+pub type Homography = [[f64; 3]; 3];
 
 fn locate_corners(points: (Point, Point, Point, Point)) -> models::RectangleCoordinates {
     let (p1, p2, p3, p4) = points;
@@ -36,3 +43,78 @@ pub fn get_rectangle_coordinates(
         _ => None,
     }
 }
+
+fn distance(point1: Point, point2: Point) -> i32 {
+    ((point1.x - point2.x).pow(2) + (point1.y - point2.y).pow(2)).isqrt()
+}
+
+/// The width and height of the rectangle once it is viewed straight on, taking the longest of
+/// each pair of opposite sides
+pub fn get_rectangle_dimensions(coordinates: &models::RectangleCoordinates) -> (i32, i32) {
+    let width_bottom = distance(coordinates.bottom_right, coordinates.bottom_left);
+    let width_top = distance(coordinates.top_right, coordinates.top_left);
+    let height_right = distance(coordinates.top_right, coordinates.bottom_right);
+    let height_left = distance(coordinates.top_left, coordinates.bottom_left);
+
+    (max(width_bottom, width_top), max(height_right, height_left))
+}
+
+pub fn homography_from_mat(matrix: &Mat) -> Result<Homography, Error> {
+    let mut homography: Homography = [[0.; 3]; 3];
+
+    for row in 0..3 {
+        for column in 0..3 {
+            homography[row][column] = *matrix.at_2d::<f64>(row as i32, column as i32)?;
+        }
+    }
+
+    Ok(homography)
+}
+
+/// Composes the homography with a scaling of its source coordinates, so that it can be applied to
+/// points in an image that is `1 / scale_x` by `1 / scale_y` the size of the one it was built for
+pub fn scale_homography_source(homography: &Homography, scale_x: f64, scale_y: f64) -> Homography {
+    homography.map(|[a, b, c]| [a * scale_x, b * scale_y, c])
+}
+
+pub fn invert_homography(homography: &Homography) -> Option<Homography> {
+    let [[a, b, c], [d, e, f], [g, h, i]] = *homography;
+
+    let determinant = a * (e * i - f * h) - b * (d * i - f * g) + c * (d * h - e * g);
+
+    if determinant.abs() < f64::EPSILON {
+        return None;
+    }
+
+    let inverse = [
+        [e * i - f * h, c * h - b * i, b * f - c * e],
+        [f * g - d * i, a * i - c * g, c * d - a * f],
+        [d * h - e * g, b * g - a * h, a * e - b * d],
+    ];
+
+    Some(inverse.map(|row| row.map(|value| value / determinant)))
+}
+
+pub fn apply_homography(homography: &Homography, point: Point2f) -> Point2f {
+    let (x, y) = (point.x as f64, point.y as f64);
+    let [[a, b, c], [d, e, f], [g, h, i]] = *homography;
+
+    let w = g * x + h * y + i;
+
+    Point2f::new(
+        ((a * x + b * y + c) / w) as f32,
+        ((d * x + e * y + f) / w) as f32,
+    )
+}
+
+/// Maps the corners of an axis aligned rectangle through the homography
+pub fn map_rectangle(homography: &Homography, rect: Rect2i) -> Quadrilateral {
+    let corner = |x: i32, y: i32| apply_homography(homography, Point2f::new(x as f32, y as f32));
+
+    Quadrilateral {
+        top_left: corner(rect.x, rect.y),
+        top_right: corner(rect.x + rect.width, rect.y),
+        bottom_right: corner(rect.x + rect.width, rect.y + rect.height),
+        bottom_left: corner(rect.x, rect.y + rect.height),
+    }
+}
@@ -0,0 +1,701 @@
+use opencv::{
+    Error,
+    core::{Mat, MatTraitConst, Point, Rect2i, Size, Vector},
+    imgproc::{
+        self, THRESH_BINARY_INV, THRESH_OTSU, bounding_rect, dilate_def, find_contours_def,
+        get_structuring_element_def, threshold,
+    },
+};
+
+use std::sync::Arc;
+
+use crate::{
+    classifier::{ClassifierMode, DigitClassifier},
+    config::{DigitLocationConfig, SegmentConfig, SegmentSampling},
+    debug::BpmOcrDebugOutputter,
+    digit_extractor,
+    models::{DebuggerTrace, ProcessingError, ReadingIdentificationError},
+};
+
+pub use crate::digit_extractor::DigitParseResult;
+
+/// Reads the numbers shown on any seven segment display, whether it belongs to a blood pressure
+/// monitor, a thermometer or a set of scales. The display is expected to have already been
+/// cropped and perspective corrected so that it fills the image, with dark segments on a light
+/// background.
+pub struct SevenSegmentReader {
+    digit_config: DigitLocationConfig,
+    segment_config: SegmentConfig,
+    classifier: Option<(Arc<DigitClassifier>, ClassifierMode)>,
+}
+
+/// The shapes found on a highlighted display, split by whether they are tall enough to be digits
+#[derive(Clone, Debug, PartialEq)]
+pub struct DisplayShapes {
+    pub digits: Vec<Rect2i>,
+    /// Shapes too short to be digits, e.g. decimal points, colons and icons
+    pub small_shapes: Vec<Rect2i>,
+}
+
+/// A digit found on the display and what its lit up segments were read as
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecognisedDigit {
+    pub location: Rect2i,
+    pub result: DigitParseResult,
+}
+
+/// A run of neighbouring digits on one row of the display, read as a single number
+#[derive(Clone, Debug, PartialEq)]
+pub struct SevenSegmentNumber {
+    /// The number's digits from left to right
+    pub digits: Vec<RecognisedDigit>,
+    /// How many of the digits come after a decimal point
+    pub decimal_places: u32,
+    /// The bounding box of all of the number's digits
+    pub location: Rect2i,
+}
+
+/// The numbers on one row of the display, from left to right
+#[derive(Clone, Debug, PartialEq)]
+pub struct SevenSegmentRow {
+    pub numbers: Vec<SevenSegmentNumber>,
+    /// The bounding box of all of the row's digits
+    pub location: Rect2i,
+}
+
+impl SevenSegmentNumber {
+    /// The number as it was shown on the display, or nothing if any of its digits could not be
+    /// recognised. Fails if the number has too many digits to be held.
+    pub fn value(&self) -> Result<Option<f64>, ProcessingError> {
+        let mut result: u64 = 0;
+
+        for digit in &self.digits {
+            match &digit.result {
+                DigitParseResult::Digit(digit_confidence) => {
+                    result = result
+                        .checked_mul(10)
+                        .and_then(|result| result.checked_add(digit_confidence.digit as u64))
+                        .ok_or(ProcessingError::AppError(
+                            ReadingIdentificationError::InternalError(
+                                "The number has too many digits to be held",
+                            ),
+                        ))?;
+                }
+                DigitParseResult::UnrecognisedSegments(_) | DigitParseResult::Symbol { .. } => {
+                    return Ok(None);
+                }
+            }
+        }
+
+        Ok(Some(
+            result as f64 / 10_f64.powi(self.decimal_places as i32),
+        ))
+    }
+}
+
+impl Default for SevenSegmentReader {
+    fn default() -> Self {
+        SevenSegmentReader::new(DigitLocationConfig::default(), SegmentConfig::default())
+    }
+}
+
+impl SevenSegmentReader {
+    pub fn new(digit_config: DigitLocationConfig, segment_config: SegmentConfig) -> Self {
+        SevenSegmentReader {
+            digit_config: digit_config,
+            segment_config: segment_config,
+            classifier: None,
+        }
+    }
+
+    /// Reads digits with a trained classifier, either instead of the segment rules or for the
+    /// digits they cannot read
+    pub fn with_classifier(
+        mut self,
+        classifier: Arc<DigitClassifier>,
+        mode: ClassifierMode,
+    ) -> Self {
+        self.classifier = Some((classifier, mode));
+        self
+    }
+
+    /// Reads every row of numbers on the display, from top to bottom
+    pub fn read<T: BpmOcrDebugOutputter>(
+        self: &Self,
+        image: &Mat,
+        debugging_session: &DebuggerTrace<T>,
+    ) -> Result<Vec<SevenSegmentRow>, ProcessingError> {
+        let highlighted_digits = self.highlight(image, debugging_session)?;
+        let shapes = self.find_shapes(&highlighted_digits)?;
+
+        debugging_session.debugger.debug_digit_locations(
+            &debugging_session.unique_trace_name,
+            &highlighted_digits,
+            &shapes.digits,
+        )?;
+
+        let mut rows: Vec<SevenSegmentRow> = Vec::new();
+
+        for row in self.group_into_rows(shapes.digits, highlighted_digits.rows()) {
+            let mut numbers: Vec<SevenSegmentNumber> = Vec::new();
+
+            for number_digits in self.split_into_numbers(row) {
+                let mut digits: Vec<RecognisedDigit> = Vec::with_capacity(number_digits.len());
+
+                for location in &number_digits {
+                    digits.push(RecognisedDigit {
+                        location: *location,
+                        result: self.read_digit(&highlighted_digits, *location)?,
+                    });
+                }
+
+                numbers.push(SevenSegmentNumber {
+                    digits,
+                    decimal_places: self
+                        .find_decimal_places(&number_digits, &shapes.small_shapes)
+                        .unwrap_or(0),
+                    location: bounding_box(&number_digits),
+                });
+            }
+
+            let number_locations: Vec<Rect2i> =
+                numbers.iter().map(|number| number.location).collect();
+
+            rows.push(SevenSegmentRow {
+                location: bounding_box(&number_locations),
+                numbers,
+            });
+        }
+
+        Ok(rows)
+    }
+
+    /// Turns the dark segments of the display white and everything else black, joining up the
+    /// segments of each digit so that every digit is a single shape
+    pub fn highlight<T: BpmOcrDebugOutputter>(
+        self: &Self,
+        image: &Mat,
+        debugging_session: &DebuggerTrace<T>,
+    ) -> Result<Mat, ProcessingError> {
+        let mut thresholed_image = Mat::default();
+
+        threshold(
+            image,
+            &mut thresholed_image,
+            0.,
+            255.,
+            THRESH_BINARY_INV | THRESH_OTSU,
+        )?;
+
+        debugging_session
+            .debugger
+            .debug_digits_before_morph(&debugging_session.unique_trace_name, &thresholed_image)?;
+
+        let mut dilated_image = Mat::default();
+
+        // Fill in the gaps in the middle of the digits on the LCD screen to make it easier to identify the full digit
+        let kernel_size = self.digit_config.dilation_kernel_size;
+        let dilation_kernel =
+            get_structuring_element_def(imgproc::MORPH_RECT, Size::new(kernel_size, kernel_size))?;
+        dilate_def(&thresholed_image, &mut dilated_image, &dilation_kernel)?;
+
+        debugging_session
+            .debugger
+            .debug_digits_after_dilation(&debugging_session.unique_trace_name, &dilated_image)?;
+
+        return Ok(dilated_image);
+    }
+
+    /// Finds the bounding box of every shape on the highlighted display, split by whether it is tall enough to be a digit
+    pub fn find_shapes(self: &Self, highlighted: &Mat) -> Result<DisplayShapes, ProcessingError> {
+        let mut contours_output: Vector<Vector<Point>> = Vector::new();
+        find_contours_def(
+            highlighted,
+            &mut contours_output,
+            imgproc::RETR_EXTERNAL,
+            imgproc::CHAIN_APPROX_SIMPLE,
+        )?;
+
+        let shapes: Vec<Rect2i> = contours_output
+            .into_iter()
+            .map(|contour| {
+                return bounding_rect(&contour);
+            })
+            .filter(|possible_shape| match possible_shape {
+                Ok(rect) => rect.y != 0 && rect.x != 0,
+                _ => true, // Make sure errors are propagated
+            })
+            .collect::<Result<Vec<Rect2i>, Error>>()?;
+
+        let (digits, small_shapes) = shapes
+            .into_iter()
+            .partition(|shape| self.is_tall_enough_for_digit(shape, highlighted.rows()));
+
+        return Ok(DisplayShapes {
+            digits,
+            small_shapes,
+        });
+    }
+
+    fn is_tall_enough_for_digit(self: &Self, shape: &Rect2i, display_height: i32) -> bool {
+        let min_digit_height =
+            (self.digit_config.min_digit_height_ratio * display_height as f32) as i32;
+
+        shape.height > min_digit_height
+    }
+
+    /// Reads the digit at the given location of the highlighted display from its lit up segments,
+    /// or with the classifier if there is one
+    pub fn read_digit(
+        self: &Self,
+        highlighted: &Mat,
+        location: Rect2i,
+    ) -> Result<DigitParseResult, ProcessingError> {
+        self.read_digit_sampled(highlighted, location, self.segment_config.sampling)
+    }
+
+    /// Reads the digit at the given location of the highlighted display, deciding which of its
+    /// segments are lit up with the given sampling rather than the configured one
+    pub fn read_digit_sampled(
+        self: &Self,
+        highlighted: &Mat,
+        location: Rect2i,
+        sampling: SegmentSampling,
+    ) -> Result<DigitParseResult, ProcessingError> {
+        let parse_segments =
+            || digit_extractor::parse_digit(highlighted, location, &self.segment_config, sampling);
+
+        match &self.classifier {
+            Some((classifier, ClassifierMode::Replace)) => {
+                match classifier.classify_if_agreed(highlighted, location)? {
+                    Some(digit_confidence) => Ok(DigitParseResult::Digit(digit_confidence)),
+                    None => Ok(DigitParseResult::UnrecognisedSegments(
+                        digit_extractor::lit_segments(
+                            highlighted,
+                            location,
+                            &self.segment_config,
+                            sampling,
+                        )?,
+                    )),
+                }
+            }
+            Some((classifier, ClassifierMode::Fallback)) => match parse_segments()? {
+                DigitParseResult::UnrecognisedSegments(segments) => {
+                    match classifier.classify_if_agreed(highlighted, location)? {
+                        Some(digit_confidence) => Ok(DigitParseResult::Digit(digit_confidence)),
+                        None => Ok(DigitParseResult::UnrecognisedSegments(segments)),
+                    }
+                }
+                result => Ok(result),
+            },
+            None => parse_segments(),
+        }
+    }
+
+    /// Reads the character at the given location of the highlighted display, which may be one of
+    /// the font's letters or its dash as well as a digit, e.g. as part of an error code
+    pub fn read_character(
+        self: &Self,
+        highlighted: &Mat,
+        location: Rect2i,
+    ) -> Result<DigitParseResult, ProcessingError> {
+        self.read_character_sampled(highlighted, location, self.segment_config.sampling)
+    }
+
+    /// Reads the character at the given location of the highlighted display, deciding which of its
+    /// segments are lit up with the given sampling rather than the configured one
+    pub fn read_character_sampled(
+        self: &Self,
+        highlighted: &Mat,
+        location: Rect2i,
+        sampling: SegmentSampling,
+    ) -> Result<DigitParseResult, ProcessingError> {
+        let symbol =
+            digit_extractor::parse_symbol(highlighted, location, &self.segment_config, sampling)?;
+
+        match symbol {
+            Some(symbol) => Ok(symbol),
+            None => self.read_digit_sampled(highlighted, location, sampling),
+        }
+    }
+
+    pub fn segment_config(self: &Self) -> &SegmentConfig {
+        &self.segment_config
+    }
+
+    /// Finds how many of a number's digits come after a decimal point, by looking for a small shape
+    /// at the bottom of the gap between two of its digits
+    pub fn find_decimal_places(
+        self: &Self,
+        digits: &[Rect2i],
+        small_shapes: &[Rect2i],
+    ) -> Option<u32> {
+        let max_size_ratio = self.digit_config.max_decimal_point_size_ratio;
+
+        let position = digits.windows(2).position(|pair| {
+            let (before, after) = (pair[0], pair[1]);
+            let digit_height = before.height.max(after.height) as f32;
+            let bottom = before.y.max(after.y) + before.height.max(after.height);
+
+            small_shapes.iter().any(|shape| {
+                let centre_x = shape.x + shape.width / 2;
+                let centre_y = shape.y + shape.height / 2;
+
+                shape.height as f32 <= max_size_ratio * digit_height
+                    && shape.width as f32 <= max_size_ratio * digit_height
+                    && centre_x > before.x + before.width / 2
+                    && centre_x < after.x + after.width / 2
+                    && centre_y as f32 > bottom as f32 - digit_height / 3.
+                    && centre_y as f32 <= bottom as f32 + max_size_ratio * digit_height
+            })
+        })?;
+
+        Some((digits.len() - (position + 1)) as u32)
+    }
+
+    fn group_by_similar_y_coordinate(
+        self: &Self,
+        digits: Vec<Rect2i>,
+        difference_threshold: i32,
+    ) -> Vec<Vec<Rect2i>> {
+        let mut groups: Vec<Vec<Rect2i>> = Vec::new();
+
+        'outer: for digit in digits {
+            for group in groups.iter_mut() {
+                if let Some(leader) = group.first() {
+                    if (leader.y - digit.y).abs() < difference_threshold {
+                        group.push(digit);
+                        continue 'outer;
+                    }
+                }
+            }
+
+            // No matching group found — make a new one
+            groups.push(vec![digit]);
+        }
+
+        groups
+    }
+
+    /// Groups the digits into rows from top to bottom, with each row's digits sorted from left to right
+    pub fn group_into_rows(
+        self: &Self,
+        mut digits: Vec<Rect2i>,
+        display_height: i32,
+    ) -> Vec<Vec<Rect2i>> {
+        // Sort digits by their row
+
+        digits.sort_by(|vec1, vec2| vec1.y.cmp(&vec2.y));
+
+        let row_grouping_threshold =
+            ((self.digit_config.row_grouping_ratio * display_height as f32) as i32).max(1);
+
+        let mut grouped_by_y_coordinate: Vec<Vec<Rect2i>> =
+            self.group_by_similar_y_coordinate(digits, row_grouping_threshold);
+
+        // Sort numbers by their columns
+        for group in grouped_by_y_coordinate.iter_mut() {
+            group.sort_by(|item1, item2| item1.x.cmp(&item2.x));
+        }
+
+        grouped_by_y_coordinate
+    }
+
+    /// Splits a row of digits, sorted from left to right, into separate numbers wherever the gap
+    /// between two digits is wide compared to their height
+    pub fn split_into_numbers(self: &Self, row: Vec<Rect2i>) -> Vec<Vec<Rect2i>> {
+        let mut numbers: Vec<Vec<Rect2i>> = Vec::new();
+        let mut current: Vec<Rect2i> = Vec::new();
+
+        for digit in row {
+            let is_far_from_previous = current.last().is_some_and(|previous| {
+                let digit_height = previous.height.max(digit.height) as f32;
+
+                (digit.x - (previous.x + previous.width)) as f32
+                    > self.digit_config.number_gap_ratio * digit_height
+            });
+
+            if is_far_from_previous {
+                numbers.push(std::mem::take(&mut current));
+            }
+
+            current.push(digit);
+        }
+
+        if !current.is_empty() {
+            numbers.push(current);
+        }
+
+        numbers
+    }
+}
+
+/// The smallest rectangle containing all of the given rectangles
+fn bounding_box(rects: &[Rect2i]) -> Rect2i {
+    let Some(first) = rects.first() else {
+        return Rect2i::default();
+    };
+
+    let (mut left, mut top) = (first.x, first.y);
+    let (mut right, mut bottom) = (first.x + first.width, first.y + first.height);
+
+    for rect in &rects[1..] {
+        left = left.min(rect.x);
+        top = top.min(rect.y);
+        right = right.max(rect.x + rect.width);
+        bottom = bottom.max(rect.y + rect.height);
+    }
+
+    Rect2i::new(left, top, right - left, bottom - top)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::font::{Glyph, SegmentFont, SymbolGlyph};
+    use crate::models::DigitConfidence;
+    use crate::test_drawing::{draw_glyph, strokes};
+
+    #[test]
+    fn test_decimal_places_found_from_decimal_point() {
+        let reader =
+            SevenSegmentReader::new(DigitLocationConfig::default(), SegmentConfig::default());
+
+        let digits = vec![
+            Rect2i::new(0, 0, 20, 40),
+            Rect2i::new(30, 0, 20, 40),
+            Rect2i::new(60, 0, 20, 40),
+        ];
+
+        // A dot at the bottom of the gap between the last two digits
+        assert_eq!(
+            reader.find_decimal_places(&digits, &[Rect2i::new(52, 36, 4, 4)]),
+            Some(1)
+        );
+        assert_eq!(
+            reader.find_decimal_places(&digits, &[Rect2i::new(22, 36, 4, 4)]),
+            Some(2)
+        );
+        assert_eq!(reader.find_decimal_places(&digits, &[]), None);
+
+        // The dots of a colon at the top, and shapes too big to be a decimal point, don't count
+        assert_eq!(
+            reader.find_decimal_places(&digits, &[Rect2i::new(52, 0, 4, 4)]),
+            None
+        );
+        assert_eq!(
+            reader.find_decimal_places(&digits, &[Rect2i::new(48, 28, 15, 15)]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_row_split_into_numbers() {
+        let reader = SevenSegmentReader::default();
+        let digit = |x: i32| Rect2i::new(x, 0, 20, 40);
+
+        // Gaps wider than half the digit height of 40 split the row
+        assert_eq!(
+            reader.split_into_numbers(vec![digit(0), digit(25), digit(50), digit(100), digit(125)]),
+            vec![
+                vec![digit(0), digit(25), digit(50)],
+                vec![digit(100), digit(125)],
+            ]
+        );
+        assert_eq!(
+            reader.split_into_numbers(vec![digit(0), digit(40)]),
+            vec![vec![digit(0), digit(40)]]
+        );
+        assert!(reader.split_into_numbers(Vec::new()).is_empty());
+    }
+
+    #[test]
+    fn test_bounding_box_of_rects() {
+        assert_eq!(
+            bounding_box(&[
+                Rect2i::new(10, 20, 5, 5),
+                Rect2i::new(0, 30, 5, 10),
+                Rect2i::new(30, 25, 10, 5),
+            ]),
+            Rect2i::new(0, 20, 40, 20)
+        );
+        assert_eq!(
+            bounding_box(&[Rect2i::new(3, 4, 5, 6)]),
+            Rect2i::new(3, 4, 5, 6)
+        );
+        assert_eq!(bounding_box(&[]), Rect2i::default());
+    }
+
+    #[test]
+    fn test_tailless_glyphs_read_with_fonts_that_have_them() {
+        let tailless_glyphs = [
+            Glyph::new(6, [0, 1, 0, 1, 1, 1, 1]),
+            Glyph::new(7, [1, 0, 1, 0, 0, 1, 0]),
+            Glyph::new(9, [1, 1, 1, 1, 0, 1, 0]),
+        ];
+
+        for font in [SegmentFont::all_styles(), SegmentFont::tailless()] {
+            let font_name = font.name.clone();
+            let reader = SevenSegmentReader::new(
+                DigitLocationConfig::default(),
+                SegmentConfig {
+                    font,
+                    ..SegmentConfig::default()
+                },
+            );
+
+            for glyph in &tailless_glyphs {
+                let (image, location) = draw_glyph(strokes(glyph.segments, 10));
+
+                match reader.read_digit(&image, location).unwrap() {
+                    DigitParseResult::Digit(digit) => assert_eq!(
+                        digit.digit, glyph.digit,
+                        "the tailless {} was misread with the {} font",
+                        glyph.digit, font_name
+                    ),
+                    result => panic!(
+                        "the tailless {} was read as {:?} with the {} font",
+                        glyph.digit, result, font_name
+                    ),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_partly_lit_segment_decoded_by_likelihood() {
+        let reader = SevenSegmentReader::default();
+
+        // A 3 whose top segment is too faint to count as lit, which is not a digit on its own
+        let (faint_three, location) = draw_glyph([4, 0, 10, 10, 0, 10, 10]);
+
+        let DigitParseResult::Digit(digit) = reader.read_digit(&faint_three, location).unwrap()
+        else {
+            panic!("the faint 3 was not read as a digit");
+        };
+
+        assert_eq!(digit.digit, 3);
+        assert_ne!(digit.runner_up, Some(3));
+        assert!(digit.runner_up.is_some());
+        assert!((0.0..=1.0).contains(&digit.margin));
+        assert!(digit.confidence < 0.5);
+    }
+
+    #[test]
+    fn test_letters_read_as_symbols() {
+        let reader = SevenSegmentReader::default();
+
+        let (letter_e, location) = draw_glyph(strokes(
+            SymbolGlyph::new('E', [1, 1, 0, 1, 1, 0, 1]).segments,
+            10,
+        ));
+        assert!(matches!(
+            reader.read_character(&letter_e, location).unwrap(),
+            DigitParseResult::Symbol { symbol: 'E', .. }
+        ));
+
+        // Letters are only looked for when reading characters, never digits
+        assert!(!matches!(
+            reader.read_digit(&letter_e, location).unwrap(),
+            DigitParseResult::Symbol { .. }
+        ));
+
+        let (eight, location) = draw_glyph(strokes([true; 7], 10));
+        assert!(matches!(
+            reader.read_character(&eight, location).unwrap(),
+            DigitParseResult::Digit(DigitConfidence { digit: 8, .. })
+        ));
+    }
+
+    #[test]
+    fn test_digit_with_faint_segment_not_read_as_letter() {
+        let reader = SevenSegmentReader::default();
+
+        // A 0 with a top segment too faint to count as lit has the segments of a U
+        let (faint_zero, location) = draw_glyph([1, 10, 10, 0, 10, 10, 10]);
+
+        assert!(matches!(
+            reader.read_digit(&faint_zero, location).unwrap(),
+            DigitParseResult::Digit(DigitConfidence { digit: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn test_scanlines_read_every_glyph() {
+        let mut segment_config = SegmentConfig::default();
+        segment_config.sampling = SegmentSampling::scanlines();
+
+        let reader = SevenSegmentReader::new(DigitLocationConfig::default(), segment_config);
+
+        for glyph in SegmentFont::standard().glyphs {
+            let (image, location) = draw_glyph(strokes(glyph.segments, 10));
+
+            assert!(matches!(
+                reader.read_digit(&image, location).unwrap(),
+                DigitParseResult::Digit(DigitConfidence { digit, .. }) if digit == glyph.digit
+            ));
+        }
+    }
+
+    #[test]
+    fn test_thin_glyphs_read_with_scanlines() {
+        let reader = SevenSegmentReader::default();
+
+        let (eight, location) = draw_glyph(strokes([true; 7], 3));
+
+        assert!(!matches!(
+            reader
+                .read_digit_sampled(&eight, location, SegmentSampling::zones())
+                .unwrap(),
+            DigitParseResult::Digit(DigitConfidence { digit: 8, .. })
+        ));
+
+        for glyph in SegmentFont::standard().glyphs {
+            let (image, location) = draw_glyph(strokes(glyph.segments, 3));
+
+            assert!(matches!(
+                reader.read_digit_sampled(&image, location, SegmentSampling::scanlines()).unwrap(),
+                DigitParseResult::Digit(DigitConfidence { digit, .. }) if digit == glyph.digit
+            ));
+        }
+
+        let (letter_e, location) = draw_glyph(strokes(
+            SymbolGlyph::new('E', [1, 1, 0, 1, 1, 0, 1]).segments,
+            3,
+        ));
+
+        assert!(matches!(
+            reader
+                .read_character_sampled(&letter_e, location, SegmentSampling::scanlines())
+                .unwrap(),
+            DigitParseResult::Symbol { symbol: 'E', .. }
+        ));
+    }
+
+    #[test]
+    fn test_number_too_long_to_hold_rejected() {
+        let nine = RecognisedDigit {
+            location: Rect2i::new(0, 0, 10, 20),
+            result: DigitParseResult::Digit(DigitConfidence {
+                digit: 9,
+                confidence: 1.,
+                runner_up: None,
+                margin: 1.,
+            }),
+        };
+
+        let number = |length: usize| SevenSegmentNumber {
+            digits: vec![nine.clone(); length],
+            decimal_places: 0,
+            location: Rect2i::new(0, 0, 10 * length as i32, 20),
+        };
+
+        assert_eq!(number(3).value().unwrap(), Some(999.));
+        assert!(matches!(
+            number(20).value(),
+            Err(ProcessingError::AppError(
+                ReadingIdentificationError::InternalError(_)
+            ))
+        ));
+    }
+}
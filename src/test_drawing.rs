@@ -0,0 +1,65 @@
+use opencv::{
+    core::{CV_8U, Mat, Point, Rect2i, Scalar},
+    imgproc,
+};
+
+/// How thick each segment of a glyph is drawn, as given by its lit up segments
+pub(crate) fn strokes(segments: [bool; 7], thickness: i32) -> [i32; 7] {
+    segments.map(|lit| if lit { thickness } else { 0 })
+}
+
+/// Draws a single glyph the way it looks on a highlighted LCD screen, returning the glyph's box
+pub(crate) fn draw_glyph(strokes: [i32; 7]) -> (Mat, Rect2i) {
+    let (image, locations) = draw_glyphs(&[strokes]);
+
+    (image, locations[0])
+}
+
+/// Draws a row of glyphs across the middle of a display 200 pixels tall, the way they look on a
+/// highlighted LCD screen. Each segment is drawn as thick as given, in pixels, and any segment with
+/// a thickness of 0 is left out. The box of each glyph is returned along with the display.
+pub(crate) fn draw_glyphs(glyphs: &[[i32; 7]]) -> (Mat, Vec<Rect2i>) {
+    let width = 60 * glyphs.len() as i32 + 40;
+    let mut image = Mat::new_rows_cols_with_default(200, width, CV_8U, Scalar::all(0.)).unwrap();
+
+    let locations = glyphs
+        .iter()
+        .enumerate()
+        .map(|(index, strokes)| {
+            let origin = Point::new(30 + 60 * index as i32, 60);
+
+            let segment_rects = [
+                Rect2i::new(0, 0, 40, strokes[0]),
+                Rect2i::new(0, 0, strokes[1], 40),
+                Rect2i::new(40 - strokes[2], 0, strokes[2], 40),
+                Rect2i::new(0, 40 - strokes[3] / 2, 40, strokes[3]),
+                Rect2i::new(0, 40, strokes[4], 40),
+                Rect2i::new(40 - strokes[5], 40, strokes[5], 40),
+                Rect2i::new(0, 80 - strokes[6], 40, strokes[6]),
+            ];
+
+            for (rect, stroke) in segment_rects.into_iter().zip(strokes) {
+                if *stroke > 0 {
+                    imgproc::rectangle(
+                        &mut image,
+                        Rect2i::new(
+                            rect.x + origin.x,
+                            rect.y + origin.y,
+                            rect.width,
+                            rect.height,
+                        ),
+                        Scalar::all(255.),
+                        imgproc::FILLED,
+                        imgproc::LINE_8,
+                        0,
+                    )
+                    .unwrap();
+                }
+            }
+
+            Rect2i::new(origin.x, origin.y, 40, 80)
+        })
+        .collect();
+
+    (image, locations)
+}
@@ -0,0 +1,105 @@
+use crate::{
+    config::ValidationConfig,
+    models::{BloodPressureReading, PlausibilityRule},
+};
+
+/// Checks the reading is physiologically plausible, returning the first rule it breaks if not
+pub fn validate_reading(
+    reading: &BloodPressureReading,
+    config: &ValidationConfig,
+) -> Result<(), PlausibilityRule> {
+    if !config.enabled {
+        return Ok(());
+    }
+
+    if !config.systolic.contains(reading.systolic) {
+        return Err(PlausibilityRule::SystolicOutOfRange);
+    }
+
+    if !config.diastolic.contains(reading.diastolic) {
+        return Err(PlausibilityRule::DiastolicOutOfRange);
+    }
+
+    if !config.pulse.contains(reading.pulse) {
+        return Err(PlausibilityRule::PulseOutOfRange);
+    }
+
+    if reading.diastolic >= reading.systolic {
+        return Err(PlausibilityRule::DiastolicNotBelowSystolic);
+    }
+
+    if reading.systolic - reading.diastolic < config.min_pulse_pressure {
+        return Err(PlausibilityRule::PulsePressureTooNarrow);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validation_rules() {
+        let config = ValidationConfig::default();
+
+        let validate = |systolic, diastolic, pulse| {
+            validate_reading(
+                &BloodPressureReading {
+                    systolic,
+                    diastolic,
+                    pulse,
+                },
+                &config,
+            )
+        };
+
+        assert_eq!(validate(120, 80, 70), Ok(()));
+        assert_eq!(
+            validate(300, 80, 70),
+            Err(PlausibilityRule::SystolicOutOfRange)
+        );
+        assert_eq!(
+            validate(120, 20, 70),
+            Err(PlausibilityRule::DiastolicOutOfRange)
+        );
+        assert_eq!(
+            validate(120, 80, 250),
+            Err(PlausibilityRule::PulseOutOfRange)
+        );
+        assert_eq!(
+            validate(90, 95, 70),
+            Err(PlausibilityRule::DiastolicNotBelowSystolic)
+        );
+        assert_eq!(
+            validate(90, 85, 70),
+            Err(PlausibilityRule::PulsePressureTooNarrow)
+        );
+
+        // Each rule is only checked once the ones before it pass
+        assert_eq!(
+            validate(300, 20, 250),
+            Err(PlausibilityRule::SystolicOutOfRange)
+        );
+        assert_eq!(
+            validate(80, 80, 250),
+            Err(PlausibilityRule::PulseOutOfRange)
+        );
+    }
+
+    #[test]
+    fn test_validation_disabled() {
+        let config = ValidationConfig {
+            enabled: false,
+            ..ValidationConfig::default()
+        };
+
+        let reading = BloodPressureReading {
+            systolic: 80,
+            diastolic: 120,
+            pulse: 500,
+        };
+
+        assert_eq!(validate_reading(&reading, &config), Ok(()));
+    }
+}